@@ -0,0 +1,56 @@
+use std::fmt;
+
+use crate::disasm::EncodeError;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AsmErrorKind {
+    /// the line doesn't follow the supported NASM syntax
+    Syntax(String),
+    UnknownMnemonic(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    /// a memory operand whose size can't be inferred from the other operand
+    UnsizedOperand,
+    /// an immediate, displacement or branch target that doesn't fit its field
+    OutOfRange(i64),
+    /// branch sizes kept changing the position of labels
+    UnstableLayout,
+    /// the operands don't fit any encoding of the operation
+    Encode(EncodeError),
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            Self::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{}`", name),
+            Self::UndefinedLabel(name) => write!(f, "undefined label `{}`", name),
+            Self::DuplicateLabel(name) => write!(f, "label `{}` defined more than once", name),
+            Self::UnsizedOperand => write!(f, "operation size not specified"),
+            Self::OutOfRange(value) => write!(f, "value {} out of range", value),
+            Self::UnstableLayout => write!(f, "branch sizes don't settle"),
+            Self::Encode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AsmError {
+    pub kind: AsmErrorKind,
+    /// 1-based line number in the source
+    pub line: usize,
+}
+
+impl AsmError {
+    pub fn new(kind: AsmErrorKind, line: usize) -> Self {
+        Self { kind, line }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for AsmError {}
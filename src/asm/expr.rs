@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use super::{parser::Token, AsmErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    /// `$`, the offset of the current instruction
+    Here,
+    Label(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+}
+
+/// Values the labels and `$` currently stand for
+pub struct Scope<'a> {
+    pub here: usize,
+    pub labels: &'a HashMap<String, usize>,
+}
+
+impl Expr {
+    pub fn eval(&self, scope: &Scope) -> Result<i64, AsmErrorKind> {
+        Ok(match self {
+            Self::Number(x) => *x,
+            Self::Here => scope.here as i64,
+            Self::Label(name) => *scope
+                .labels
+                .get(name)
+                .ok_or_else(|| AsmErrorKind::UndefinedLabel(name.clone()))?
+                as i64,
+            Self::Neg(x) => x.eval(scope)?.wrapping_neg(),
+            Self::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(scope)?, rhs.eval(scope)?);
+                match op {
+                    BinOp::Add => lhs.wrapping_add(rhs),
+                    BinOp::Sub => lhs.wrapping_sub(rhs),
+                    BinOp::Mul => lhs.wrapping_mul(rhs),
+                    BinOp::Div => lhs
+                        .checked_div(rhs)
+                        .ok_or_else(|| AsmErrorKind::Syntax("division by zero".to_string()))?,
+                }
+            }
+        })
+    }
+}
+
+/// Parses the whole token slice as an expression built from numbers, labels, `$`, unary minus,
+/// `+ - * /` and parentheses
+pub fn parse_expr(tokens: &[Token]) -> Result<Expr, AsmErrorKind> {
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(token) => Err(AsmErrorKind::Syntax(format!("unexpected {}", token))),
+    }
+}
+
+struct ExprParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl ExprParser<'_> {
+    fn next_if(&mut self, punct: char) -> bool {
+        if self.tokens.get(self.pos) == Some(&Token::Punct(punct)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, AsmErrorKind> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.next_if('+') {
+                BinOp::Add
+            } else if self.next_if('-') {
+                BinOp::Sub
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, AsmErrorKind> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.next_if('*') {
+                BinOp::Mul
+            } else if self.next_if('/') {
+                BinOp::Div
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, AsmErrorKind> {
+        if self.next_if('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.next_if('+') {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, AsmErrorKind> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| AsmErrorKind::Syntax("expected expression".to_string()))?;
+        self.pos += 1;
+        match token {
+            Token::Number(x) => Ok(Expr::Number(*x)),
+            Token::Ident(name) => Ok(Expr::Label(name.clone())),
+            Token::Punct('$') => Ok(Expr::Here),
+            Token::Punct('(') => {
+                let expr = self.sum()?;
+                if self.next_if(')') {
+                    Ok(expr)
+                } else {
+                    Err(AsmErrorKind::Syntax("expected `)`".to_string()))
+                }
+            }
+            token => Err(AsmErrorKind::Syntax(format!("unexpected {}", token))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::parser::tokenize;
+
+    fn eval(text: &str) -> i64 {
+        let labels = HashMap::from([("label".to_string(), 16)]);
+        let scope = Scope {
+            here: 4,
+            labels: &labels,
+        };
+        parse_expr(&tokenize(text).unwrap())
+            .unwrap()
+            .eval(&scope)
+            .unwrap()
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(eval("64*4 + 4*64 + 4"), 516);
+        assert_eq!(eval("-(2 + 3) * -2"), 10);
+        assert_eq!(eval("0x10 - 0b11 + 0ah"), 23);
+        assert_eq!(eval("$+2+-7"), -1);
+        assert_eq!(eval("label - $"), 12);
+    }
+}
@@ -0,0 +1,476 @@
+mod error;
+mod expr;
+mod parser;
+
+pub use error::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    disasm::encode,
+    fields::{CsIp, Data, EffectiveAddress, Inc, Operand, Operation, Register, Wide},
+    instruction::{Inst, InstructionPrefix},
+};
+use expr::{Expr, Scope};
+use parser::{conflicting_prefixes, parse_line, Arg, DataValue, Item, JumpSize, Memory, Statement};
+
+/// Upper bound on layout passes; each pass can only turn more short jumps into near ones
+const MAX_PASSES: usize = 64;
+
+fn is_short_branch(op: Operation) -> bool {
+    use Operation::*;
+    matches!(
+        op,
+        JE | JL
+            | JLE
+            | JB
+            | JBE
+            | JP
+            | JO
+            | JS
+            | JNE
+            | JNL
+            | JNLE
+            | JNB
+            | JNBE
+            | JNP
+            | JNO
+            | JNS
+            | LOOP
+            | LOOPZ
+            | LOOPNZ
+            | JCXZ
+    )
+}
+
+/// Operations whose memory operand has no size of its own
+fn is_unsized_memory(op: Operation) -> bool {
+    use Operation::*;
+    matches!(
+        op,
+        Push | Pop | Call | CallFar | Jmp | JmpFar | LEA | LDS | LES
+    )
+}
+
+fn is_shift(op: Operation) -> bool {
+    use Operation::*;
+    matches!(op, SHL | SHR | SAR | ROL | ROR | RCL | RCR)
+}
+
+fn in_range(value: i64, min: i64, max: i64) -> Result<i64, AsmErrorKind> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(AsmErrorKind::OutOfRange(value))
+    }
+}
+
+fn byte(value: i64) -> Result<u8, AsmErrorKind> {
+    Ok(in_range(value, i8::MIN.into(), u8::MAX.into())? as u8)
+}
+
+fn word(value: i64) -> Result<u16, AsmErrorKind> {
+    Ok(in_range(value, i16::MIN.into(), u16::MAX.into())? as u16)
+}
+
+fn operand_size(arg: &Arg) -> Option<Wide> {
+    match arg {
+        Arg::Register(reg) => Some(reg.is_wide().into()),
+        Arg::SR(_) => Some(Wide::Word),
+        Arg::Memory(memory) => memory.size,
+        Arg::Immediate(size, _) => *size,
+        Arg::Far(..) => None,
+    }
+}
+
+struct Assembler<'a> {
+    labels: &'a HashMap<String, usize>,
+    /// unqualified jumps that didn't fit in a short jump in an earlier pass
+    near_jumps: &'a HashSet<usize>,
+    /// out of range short branches are only an error once the layout has settled
+    strict: bool,
+}
+
+impl Assembler<'_> {
+    fn eval(&self, expr: &Expr, offset: usize) -> Result<i64, AsmErrorKind> {
+        expr.eval(&Scope {
+            here: offset,
+            labels: self.labels,
+        })
+    }
+
+    fn effective_address(
+        &self,
+        memory: &Memory,
+        wide: Wide,
+        offset: usize,
+    ) -> Result<EffectiveAddress, AsmErrorKind> {
+        use Register::*;
+        let disp = match &memory.disp {
+            Some(disp) => Some(word(self.eval(disp, offset)?)?),
+            None => None,
+        };
+        // like NASM, drop zero displacements where the encoding allows it
+        let opt_disp = disp.filter(|&x| x != 0);
+        let mut registers = memory.registers.clone();
+        registers.sort_by_key(|reg| [BX, BP, SI, DI].iter().position(|x| x == reg));
+        Ok(match registers[..] {
+            [] => EffectiveAddress::DirectAddress(disp.unwrap_or(0), wide),
+            [BX, SI] => EffectiveAddress::BX_SI(opt_disp, wide),
+            [BX, DI] => EffectiveAddress::BX_DI(opt_disp, wide),
+            [BP, SI] => EffectiveAddress::BP_SI(opt_disp, wide),
+            [BP, DI] => EffectiveAddress::BP_DI(opt_disp, wide),
+            [SI] => EffectiveAddress::SI(opt_disp, wide),
+            [DI] => EffectiveAddress::DI(opt_disp, wide),
+            [BP] => EffectiveAddress::BP(disp.unwrap_or(0), wide),
+            [BX] => EffectiveAddress::BX(opt_disp, wide),
+            _ => {
+                return Err(AsmErrorKind::Syntax(
+                    "invalid combination of address registers".to_string(),
+                ))
+            }
+        })
+    }
+
+    fn data(&self, size: Wide, expr: &Expr, offset: usize) -> Result<Data, AsmErrorKind> {
+        let value = self.eval(expr, offset)?;
+        match size {
+            Wide::Byte => Ok(Data::U8(byte(value)?)),
+            Wide::Word => Ok(Data::U16(word(value)?)),
+            Wide::None => Err(AsmErrorKind::UnsizedOperand),
+        }
+    }
+
+    fn branch(
+        &self,
+        idx: usize,
+        stmt: &Statement,
+        target: &Expr,
+        offset: usize,
+    ) -> Result<Inst, AsmErrorKind> {
+        let op = stmt.operation;
+        let prefix_len = usize::from(stmt.prefix.is_some());
+        let target = self.eval(target, offset)?;
+        let relaxable = op == Operation::Jmp && stmt.jump.is_none();
+        let short = is_short_branch(op)
+            || stmt.jump == Some(JumpSize::Short)
+            || (relaxable && !self.near_jumps.contains(&idx));
+        let inc = if short {
+            let inc = target - (offset + prefix_len + 2) as i64;
+            if self.strict || relaxable {
+                in_range(inc, i8::MIN.into(), i8::MAX.into())?;
+            }
+            Inc::I8(inc as i8)
+        } else {
+            // near branches wrap around within the segment
+            Inc::I16((target - (offset + prefix_len + 3) as i64) as i16)
+        };
+        let mut inst = Inst::with_operand(op, inc.into());
+        inst.prefix = stmt.prefix;
+        Ok(inst)
+    }
+
+    fn instruction(
+        &self,
+        idx: usize,
+        stmt: &Statement,
+        offset: usize,
+    ) -> Result<Inst, AsmErrorKind> {
+        let op = stmt.operation;
+        let mut inst = match &stmt.operands[..] {
+            [Arg::Immediate(None, target)]
+                if is_short_branch(op) || matches!(op, Operation::Jmp | Operation::Call) =>
+            {
+                self.branch(idx, stmt, target, offset)?
+            }
+            operands => {
+                // the accumulator (not the port) sizes IN / OUT, the operand (not the count)
+                // sizes shifts
+                let size = match (op, operands) {
+                    (Operation::OUT, [_, acc]) => operand_size(acc),
+                    (op, [first, ..]) if op == Operation::IN || is_shift(op) => operand_size(first),
+                    _ => operands.iter().find_map(operand_size),
+                };
+                let has_sr = operands.iter().any(|arg| matches!(arg, Arg::SR(_)));
+                let mut segment = None;
+                let mut resolved = Vec::new();
+                for (pos, arg) in operands.iter().enumerate() {
+                    resolved.push(match arg {
+                        Arg::Register(reg) => Operand::Register(*reg),
+                        Arg::SR(sr) => Operand::SR(*sr),
+                        Arg::Memory(memory) => {
+                            segment = memory.segment.or(segment);
+                            let wide = if is_unsized_memory(op) || has_sr {
+                                Wide::None
+                            } else {
+                                size.ok_or(AsmErrorKind::UnsizedOperand)?
+                            };
+                            self.effective_address(memory, wide, offset)?.into()
+                        }
+                        Arg::Immediate(_, expr) => {
+                            let size = match op {
+                                Operation::IN | Operation::OUT | Operation::INT => Wide::Byte,
+                                Operation::Ret | Operation::RetFar => Wide::Word,
+                                op if is_shift(op) && pos == 1 => Wide::Byte,
+                                _ => size.ok_or(AsmErrorKind::UnsizedOperand)?,
+                            };
+                            self.data(size, expr, offset)?.into()
+                        }
+                        Arg::Far(cs, ip) => CsIp {
+                            code_segment: word(self.eval(cs, offset)?)?,
+                            instruction_pointer: word(self.eval(ip, offset)?)?,
+                        }
+                        .into(),
+                    });
+                }
+                let mut resolved = resolved.into_iter();
+                let mut inst = Inst::new(op);
+                inst.first = resolved.next();
+                inst.second = resolved.next();
+                if resolved.next().is_some() {
+                    return Err(AsmErrorKind::Syntax("too many operands".to_string()));
+                }
+                match op {
+                    Operation::XCHG => normalize_xchg(&mut inst),
+                    Operation::TEST => normalize_test(&mut inst),
+                    _ => (),
+                }
+                inst.prefix = match (stmt.prefix, segment) {
+                    (prefix, None) => prefix,
+                    (None, Some(sr)) => Some(InstructionPrefix::SegmentOverride(sr)),
+                    (Some(prefix), Some(sr)) => Some(
+                        prefix
+                            .join(InstructionPrefix::SegmentOverride(sr))
+                            .ok_or_else(conflicting_prefixes)?,
+                    ),
+                };
+                inst
+            }
+        };
+        let bytes = encode(&inst).map_err(AsmErrorKind::Encode)?;
+        inst.set_bytes(&bytes);
+        Ok(inst)
+    }
+
+    fn data_bytes(
+        &self,
+        wide: bool,
+        values: &[DataValue],
+        offset: usize,
+    ) -> Result<Vec<Inst>, AsmErrorKind> {
+        let mut bytes = Vec::new();
+        for value in values {
+            match value {
+                DataValue::Bytes(x) => bytes.extend(x),
+                DataValue::Expr(expr) if wide => {
+                    bytes.extend(word(self.eval(expr, offset + bytes.len())?)?.to_le_bytes())
+                }
+                DataValue::Expr(expr) => bytes.push(byte(self.eval(expr, offset + bytes.len())?)?),
+            }
+        }
+        Ok(bytes.into_iter().map(Inst::data_byte).collect())
+    }
+}
+
+/// The decoder reports XCHG as `reg, rm` (or `ax, reg`), while it's printed the other way
+/// around; mirror that so that printed instructions assemble back to themselves
+fn normalize_xchg(inst: &mut Inst) {
+    std::mem::swap(&mut inst.first, &mut inst.second);
+    let swap = match (inst.first, inst.second) {
+        (Some(Operand::Register(reg)), Some(Operand::Register(Register::AX))) => reg.is_wide(),
+        (Some(Operand::Register(_)), _) => false,
+        _ => true,
+    };
+    if swap {
+        std::mem::swap(&mut inst.first, &mut inst.second);
+    }
+}
+
+/// TEST only has a `rm, reg` encoding, NASM takes the operands in either order
+fn normalize_test(inst: &mut Inst) {
+    if let (Some(Operand::Register(_)), Some(Operand::EffectiveAddress(_))) =
+        (inst.first, inst.second)
+    {
+        std::mem::swap(&mut inst.first, &mut inst.second);
+    }
+}
+
+/// Assembles NASM-style 8086 source (the subset `write_8086` emits, plus labels, `db`/`dw` and
+/// a few common aliases) into instructions with their sizes and machine code set
+pub fn assemble_8086(source: &str) -> Result<Vec<Inst>, AsmError> {
+    let mut items = Vec::new();
+    let mut scope = String::new();
+    for (idx, line) in source.lines().enumerate() {
+        let parsed = parse_line(line, &mut scope).map_err(|kind| AsmError::new(kind, idx + 1))?;
+        items.extend(parsed.into_iter().map(|item| (idx + 1, item)));
+    }
+
+    let mut labels = HashMap::new();
+    for (line, item) in &items {
+        if let Item::Label(name) = item {
+            if labels.insert(name.clone(), 0).is_some() {
+                return Err(AsmError::new(
+                    AsmErrorKind::DuplicateLabel(name.clone()),
+                    *line,
+                ));
+            }
+        }
+    }
+
+    // lay the code out until label positions stop moving
+    let mut near_jumps = HashSet::new();
+    for _ in 0..MAX_PASSES {
+        let assembler = Assembler {
+            labels: &labels,
+            near_jumps: &near_jumps,
+            strict: false,
+        };
+        let mut next_labels = HashMap::new();
+        let mut next_near_jumps = near_jumps.clone();
+        let mut offset = 0;
+        for (idx, (line, item)) in items.iter().enumerate() {
+            let size = match item {
+                Item::Label(name) => {
+                    next_labels.insert(name.clone(), offset);
+                    0
+                }
+                Item::Instruction(stmt) => match assembler.instruction(idx, stmt, offset) {
+                    Ok(inst) => inst.size().unwrap_or(0),
+                    // a jump without size qualifier that doesn't reach becomes a near one
+                    Err(AsmErrorKind::OutOfRange(_))
+                        if stmt.operation == Operation::Jmp
+                            && stmt.jump.is_none()
+                            && !near_jumps.contains(&idx) =>
+                    {
+                        next_near_jumps.insert(idx);
+                        usize::from(stmt.prefix.is_some()) + 3
+                    }
+                    Err(kind) => return Err(AsmError::new(kind, *line)),
+                },
+                Item::Data { wide, values } => assembler
+                    .data_bytes(*wide, values, offset)
+                    .map_err(|kind| AsmError::new(kind, *line))?
+                    .len(),
+            };
+            offset += size;
+        }
+        if next_labels == labels && next_near_jumps == near_jumps {
+            let assembler = Assembler {
+                labels: &labels,
+                near_jumps: &near_jumps,
+                strict: true,
+            };
+            return assemble_items(&assembler, &items);
+        }
+        labels = next_labels;
+        near_jumps = next_near_jumps;
+    }
+    let line = items.last().map_or(0, |(line, _)| *line);
+    Err(AsmError::new(AsmErrorKind::UnstableLayout, line))
+}
+
+fn assemble_items(assembler: &Assembler, items: &[(usize, Item)]) -> Result<Vec<Inst>, AsmError> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    for (idx, (line, item)) in items.iter().enumerate() {
+        let assembled = match item {
+            Item::Label(_) => Ok(Vec::new()),
+            Item::Instruction(stmt) => assembler
+                .instruction(idx, stmt, offset)
+                .map(|inst| vec![inst]),
+            Item::Data { wide, values } => assembler.data_bytes(*wide, values, offset),
+        }
+        .map_err(|kind| AsmError::new(kind, *line))?;
+        offset += assembled.iter().filter_map(Inst::size).sum::<usize>();
+        instructions.extend(assembled);
+    }
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble_8086(source)
+            .unwrap()
+            .iter()
+            .flat_map(|inst| inst.bytes().to_vec())
+            .collect()
+    }
+
+    fn error(source: &str) -> AsmError {
+        assemble_8086(source).unwrap_err()
+    }
+
+    #[test]
+    fn assemble() {
+        let source = r#"
+bits 16
+
+start:
+    mov cx, 3
+.loop:
+    add word [bp + si + 2], -1   ; sign-extended immediate
+    xchg ax, si
+    lock not byte es:[bx]
+    loopnz .loop
+    jmp start
+    db 0x60, 'a'
+"#;
+        assert_eq!(
+            bytes(source),
+            [
+                0xb9, 0x03, 0x00, // mov cx, 3
+                0x83, 0x42, 0x02, 0xff, // add word [bp + si + 2], -1
+                0x96, // xchg ax, si
+                0xf0, 0x26, 0xf6, 0x17, // lock not byte es:[bx]
+                0xe0, 0xf5, // loopnz .loop
+                0xeb, 0xf0, // jmp short start
+                0x60, 0x61, // db
+            ]
+        );
+    }
+
+    #[test]
+    fn assemble_far_jump_becomes_near() {
+        let source = "jmp end\n".to_string() + &"db 0\n".repeat(200) + "end:";
+        let instructions = assemble_8086(&source).unwrap();
+        assert_eq!(instructions[0].bytes(), [0xe9, 0xc8, 0x00]);
+        assert_eq!(
+            bytes("jmp short $+2+-7\ncall $+3+100\nret -7"),
+            [0xeb, 0xf9, 0xe8, 0x64, 0x00, 0xc2, 0xf9, 0xff]
+        );
+    }
+
+    #[test]
+    fn assemble_prefixed_string_instructions() {
+        assert_eq!(
+            bytes("rep es movsb\ncs lodsw\nlock es inc byte [bx]"),
+            [0xf3, 0x26, 0xa4, 0x2e, 0xad, 0xf0, 0x26, 0xfe, 0x07]
+        );
+    }
+
+    #[test]
+    fn assemble_errors() {
+        assert_eq!(
+            error("mov ax, 1\nmov [bx], 1"),
+            AsmError::new(AsmErrorKind::UnsizedOperand, 2)
+        );
+        assert_eq!(
+            error("jne nowhere"),
+            AsmError::new(AsmErrorKind::UndefinedLabel("nowhere".to_string()), 1)
+        );
+        assert_eq!(
+            error("a:\na:"),
+            AsmError::new(AsmErrorKind::DuplicateLabel("a".to_string()), 2)
+        );
+        assert_eq!(
+            error("mov al, 256"),
+            AsmError::new(AsmErrorKind::OutOfRange(256), 1)
+        );
+        let far = "jcxz end\n".to_string() + &"db 0\n".repeat(128) + "end:";
+        assert_eq!(error(&far), AsmError::new(AsmErrorKind::OutOfRange(128), 1));
+        assert!(matches!(error("push al").kind, AsmErrorKind::Encode(_)));
+    }
+}
@@ -0,0 +1,546 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{
+    expr::{parse_expr, Expr},
+    AsmErrorKind,
+};
+use crate::{
+    fields::{Operation, Register, SegmentRegister, Wide},
+    instruction::InstructionPrefix,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Number(i64),
+    Str(Vec<u8>),
+    Punct(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ident(name) => write!(f, "`{}`", name),
+            Self::Number(x) => write!(f, "`{}`", x),
+            Self::Str(bytes) => write!(f, "`'{}'`", String::from_utf8_lossy(bytes)),
+            Self::Punct(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.to_ascii_lowercase();
+    if let Some(hex) = text.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else if let Some(hex) = text.strip_suffix('h') {
+        i64::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Splits a line into tokens, dropping any trailing comment
+pub fn tokenize(line: &str) -> Result<Vec<Token>, AsmErrorKind> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() => (),
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(idx, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    end = idx + c.len_utf8();
+                    chars.next();
+                }
+                let word = &line[start..end];
+                if c.is_ascii_digit() {
+                    let number = parse_number(word).ok_or_else(|| {
+                        AsmErrorKind::Syntax(format!("invalid number `{}`", word))
+                    })?;
+                    tokens.push(Token::Number(number));
+                } else {
+                    tokens.push(Token::Ident(word.to_string()));
+                }
+            }
+            '\'' | '"' => {
+                let mut bytes = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some((_, end)) if end == c => break,
+                        Some((_, x)) => {
+                            let mut buf = [0; 4];
+                            bytes.extend(x.encode_utf8(&mut buf).bytes());
+                        }
+                        None => return Err(AsmErrorKind::Syntax("unterminated string".into())),
+                    }
+                }
+                tokens.push(Token::Str(bytes));
+            }
+            ',' | '[' | ']' | ':' | '+' | '-' | '*' | '/' | '(' | ')' | '$' => {
+                tokens.push(Token::Punct(c))
+            }
+            c => {
+                return Err(AsmErrorKind::Syntax(format!(
+                    "unexpected character `{}`",
+                    c
+                )))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JumpSize {
+    Short,
+    Near,
+}
+
+/// Memory operand, `byte es:[bx + si + 4]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    pub size: Option<Wide>,
+    pub segment: Option<SegmentRegister>,
+    /// base and index registers
+    pub registers: Vec<Register>,
+    pub disp: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Register(Register),
+    SR(SegmentRegister),
+    Memory(Memory),
+    Immediate(Option<Wide>, Expr),
+    /// direct far address, `cs:ip`
+    Far(Expr, Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub prefix: Option<InstructionPrefix>,
+    pub operation: Operation,
+    pub jump: Option<JumpSize>,
+    pub operands: Vec<Arg>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataValue {
+    Expr(Expr),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Label(String),
+    Instruction(Statement),
+    /// `db` / `dw` directive
+    Data {
+        wide: bool,
+        values: Vec<DataValue>,
+    },
+}
+
+fn ident(token: Option<&Token>) -> Option<String> {
+    match token {
+        Some(Token::Ident(name)) => Some(name.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+fn register(name: &str) -> Option<Register> {
+    Register::from_str(&name.to_ascii_lowercase()).ok()
+}
+
+fn segment_register(name: &str) -> Option<SegmentRegister> {
+    SegmentRegister::from_str(&name.to_ascii_lowercase()).ok()
+}
+
+fn size(name: &str) -> Option<Wide> {
+    match name {
+        "byte" => Some(Wide::Byte),
+        "word" => Some(Wide::Word),
+        _ => None,
+    }
+}
+
+fn operation(mnemonic: &str) -> Option<Operation> {
+    let name = match mnemonic {
+        "jz" => "je",
+        "jnz" => "jne",
+        "jc" | "jnae" => "jb",
+        "jnc" | "jae" => "jnb",
+        "jna" => "jbe",
+        "ja" => "jnbe",
+        "jnge" => "jl",
+        "jge" => "jnl",
+        "jng" => "jle",
+        "jg" => "jnle",
+        "jpe" => "jp",
+        "jpo" => "jnp",
+        "loope" => "loopz",
+        "loopne" => "loopnz",
+        "sal" => "shl",
+        "retn" => "ret",
+        name => name,
+    };
+    match Operation::from_str(name) {
+        Ok(
+            Operation::Lock
+            | Operation::RepE
+            | Operation::RepNE
+            | Operation::SegmentOverrideES
+            | Operation::SegmentOverrideCS
+            | Operation::SegmentOverrideSS
+            | Operation::SegmentOverrideDS
+            | Operation::DB,
+        ) => None,
+        op => op.ok(),
+    }
+}
+
+/// Prefix written ahead of the mnemonic, including a bare segment override as in `es movsb`
+fn prefix(name: &str) -> Option<InstructionPrefix> {
+    match name {
+        "lock" => Some(InstructionPrefix::Lock),
+        "rep" | "repe" | "repz" => Some(InstructionPrefix::RepE),
+        "repne" | "repnz" => Some(InstructionPrefix::RepNE),
+        _ => segment_register(name).map(InstructionPrefix::SegmentOverride),
+    }
+}
+
+pub(super) fn conflicting_prefixes() -> AsmErrorKind {
+    AsmErrorKind::Syntax("prefixes can't be combined".to_string())
+}
+
+/// Splits tokens on commas outside of brackets
+fn split_operands(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('[') | Token::Punct('(') => depth += 1,
+            Token::Punct(']') | Token::Punct(')') => depth -= 1,
+            Token::Punct(',') if depth == 0 => {
+                operands.push(&tokens[start..idx]);
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    if start < tokens.len() || !operands.is_empty() {
+        operands.push(&tokens[start..]);
+    }
+    operands
+}
+
+fn position(tokens: &[Token], punct: char) -> Option<usize> {
+    tokens.iter().position(|t| *t == Token::Punct(punct))
+}
+
+/// Parses the inside of the brackets of a memory operand
+fn parse_address(mut tokens: &[Token], memory: &mut Memory) -> Result<(), AsmErrorKind> {
+    // NASM also accepts the segment inside the brackets, `[es:bx]`
+    if let (Some(name), Some(Token::Punct(':'))) = (ident(tokens.first()), tokens.get(1)) {
+        if let Some(sr) = segment_register(&name) {
+            memory.segment = Some(sr);
+            tokens = &tokens[2..];
+        }
+    }
+    // split into signed terms; a sign right after an operator is a unary minus
+    let mut terms: Vec<(bool, &[Token])> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut negative = false;
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => depth -= 1,
+            Token::Punct(c @ ('+' | '-'))
+                if depth == 0
+                    && idx > start
+                    && !matches!(tokens[idx - 1], Token::Punct('*' | '/' | '+' | '-')) =>
+            {
+                terms.push((negative, &tokens[start..idx]));
+                negative = *c == '-';
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    terms.push((negative, &tokens[start..]));
+
+    for (negative, term) in terms {
+        match (term, ident(term.first()).as_deref().and_then(register)) {
+            ([_], Some(reg)) if !negative => {
+                if !matches!(
+                    reg,
+                    Register::BX | Register::BP | Register::SI | Register::DI
+                ) {
+                    return Err(AsmErrorKind::Syntax(format!(
+                        "`{}` can't address memory",
+                        reg
+                    )));
+                }
+                memory.registers.push(reg);
+            }
+            _ => {
+                let mut expr = parse_expr(term)?;
+                if negative {
+                    expr = Expr::Neg(Box::new(expr));
+                }
+                memory.disp = Some(match memory.disp.take() {
+                    Some(disp) => {
+                        Expr::Binary(Box::new(disp), super::expr::BinOp::Add, Box::new(expr))
+                    }
+                    None => expr,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_operand(mut tokens: &[Token]) -> Result<Arg, AsmErrorKind> {
+    let mut wide = None;
+    if let Some(x) = ident(tokens.first()).as_deref().and_then(size) {
+        wide = Some(x);
+        tokens = &tokens[1..];
+    }
+    if tokens.is_empty() {
+        return Err(AsmErrorKind::Syntax("missing operand".to_string()));
+    }
+
+    if let Some(open) = position(tokens, '[') {
+        let mut memory = Memory {
+            size: wide,
+            segment: None,
+            registers: Vec::new(),
+            disp: None,
+        };
+        match &tokens[..open] {
+            [] => (),
+            [Token::Ident(name), Token::Punct(':')] if segment_register(name).is_some() => {
+                memory.segment = segment_register(name);
+            }
+            _ => return Err(AsmErrorKind::Syntax("invalid memory operand".to_string())),
+        }
+        if tokens.last() != Some(&Token::Punct(']')) {
+            return Err(AsmErrorKind::Syntax("expected `]`".to_string()));
+        }
+        parse_address(&tokens[open + 1..tokens.len() - 1], &mut memory)?;
+        return Ok(Arg::Memory(memory));
+    }
+
+    if let [Token::Ident(name)] = tokens {
+        if let Some(reg) = register(name) {
+            return Ok(Arg::Register(reg));
+        }
+        if let Some(sr) = segment_register(name) {
+            return Ok(Arg::SR(sr));
+        }
+    }
+    if let Some(colon) = position(tokens, ':') {
+        return Ok(Arg::Far(
+            parse_expr(&tokens[..colon])?,
+            parse_expr(&tokens[colon + 1..])?,
+        ));
+    }
+    Ok(Arg::Immediate(wide, parse_expr(tokens)?))
+}
+
+fn parse_data(wide: bool, tokens: &[Token]) -> Result<Item, AsmErrorKind> {
+    let values = split_operands(tokens)
+        .into_iter()
+        .map(|value| match value {
+            [Token::Str(bytes)] => Ok(DataValue::Bytes(bytes.clone())),
+            value => parse_expr(value).map(DataValue::Expr),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() {
+        return Err(AsmErrorKind::Syntax("missing data".to_string()));
+    }
+    Ok(Item::Data { wide, values })
+}
+
+/// Parses one line of source into the label and instruction or directive on it.
+/// `scope` is the last non-local label, which `.local` labels are relative to.
+pub fn parse_line(line: &str, scope: &mut String) -> Result<Vec<Item>, AsmErrorKind> {
+    let mut tokens = tokenize(line)?;
+    for token in tokens.iter_mut() {
+        if let Token::Ident(name) = token {
+            if name.starts_with('.') {
+                *name = format!("{}{}", scope, name);
+            }
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut tokens = &tokens[..];
+    if let [Token::Ident(name), Token::Punct(':'), rest @ ..] = tokens {
+        if register(name).is_some() || segment_register(name).is_some() {
+            return Err(AsmErrorKind::Syntax(format!("invalid label `{}`", name)));
+        }
+        if !name.contains('.') {
+            scope.clone_from(name);
+        }
+        items.push(Item::Label(name.clone()));
+        tokens = rest;
+    }
+    let Some(mut mnemonic) = ident(tokens.first()) else {
+        return match tokens.first() {
+            None => Ok(items),
+            Some(token) => Err(AsmErrorKind::Syntax(format!("unexpected {}", token))),
+        };
+    };
+    tokens = &tokens[1..];
+
+    match mnemonic.as_str() {
+        "bits" => {
+            return match tokens {
+                [Token::Number(16)] => Ok(items),
+                _ => Err(AsmErrorKind::Syntax(
+                    "only `bits 16` is supported".to_string(),
+                )),
+            }
+        }
+        "db" => {
+            items.push(parse_data(false, tokens)?);
+            return Ok(items);
+        }
+        "dw" => {
+            items.push(parse_data(true, tokens)?);
+            return Ok(items);
+        }
+        _ => (),
+    }
+
+    let mut joined: Option<InstructionPrefix> = None;
+    while let Some(next) = prefix(&mnemonic) {
+        joined = Some(match joined {
+            Some(prev) => prev.join(next).ok_or_else(conflicting_prefixes)?,
+            None => next,
+        });
+        mnemonic = ident(tokens.first())
+            .ok_or_else(|| AsmErrorKind::Syntax("expected instruction after prefix".into()))?;
+        tokens = &tokens[1..];
+    }
+    let prefix = joined;
+    let mut operation =
+        operation(&mnemonic).ok_or_else(|| AsmErrorKind::UnknownMnemonic(mnemonic.clone()))?;
+
+    let mut jump = None;
+    match ident(tokens.first()).as_deref() {
+        Some("short") => jump = Some(JumpSize::Short),
+        Some("near") => jump = Some(JumpSize::Near),
+        Some("far") => {
+            operation = match operation {
+                Operation::Call => Operation::CallFar,
+                Operation::Jmp => Operation::JmpFar,
+                _ => return Err(AsmErrorKind::Syntax("unexpected `far`".to_string())),
+            }
+        }
+        _ => (),
+    }
+    if jump.is_some() || matches!(operation, Operation::CallFar | Operation::JmpFar) {
+        tokens = &tokens[1..];
+    }
+
+    let operands = split_operands(tokens)
+        .into_iter()
+        .map(parse_operand)
+        .collect::<Result<Vec<_>, _>>()?;
+    items.push(Item::Instruction(Statement {
+        prefix,
+        operation,
+        jump,
+        operands,
+    }));
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Vec<Item> {
+        parse_line(line, &mut String::new()).unwrap()
+    }
+
+    #[test]
+    fn parse_memory_operand() {
+        let items = parse("  mov byte es:[bp + 61*64*4 - 1], 255 ; comment");
+        let [Item::Instruction(stmt)] = &items[..] else {
+            panic!("{:?}", items);
+        };
+        assert_eq!(stmt.operation, Operation::Mov);
+        let Arg::Memory(memory) = &stmt.operands[0] else {
+            panic!("{:?}", stmt);
+        };
+        assert_eq!(memory.size, Some(Wide::Byte));
+        assert_eq!(memory.segment, Some(SegmentRegister::ES));
+        assert_eq!(memory.registers, [Register::BP]);
+        assert!(matches!(
+            stmt.operands[1],
+            Arg::Immediate(None, Expr::Number(255))
+        ));
+    }
+
+    #[test]
+    fn parse_labels_and_prefixes() {
+        let mut scope = String::new();
+        assert_eq!(
+            parse_line("loop_start: rep movsb", &mut scope).unwrap(),
+            [
+                Item::Label("loop_start".to_string()),
+                Item::Instruction(Statement {
+                    prefix: Some(InstructionPrefix::RepE),
+                    operation: Operation::MOVSB,
+                    jump: None,
+                    operands: Vec::new(),
+                })
+            ]
+        );
+        assert_eq!(
+            parse_line(".LBB1_1:", &mut scope).unwrap(),
+            [Item::Label("loop_start.LBB1_1".to_string())]
+        );
+        let items = parse("jmp far [di]");
+        assert!(matches!(
+            &items[..],
+            [Item::Instruction(Statement {
+                operation: Operation::JmpFar,
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse = |line: &str| parse_line(line, &mut String::new()).err();
+        assert_eq!(
+            parse("mvo ax, bx"),
+            Some(AsmErrorKind::UnknownMnemonic("mvo".to_string()))
+        );
+        assert_eq!(
+            parse("mov ax, [cx]"),
+            Some(AsmErrorKind::Syntax(
+                "`cx` can't address memory".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("bits 32"),
+            Some(AsmErrorKind::Syntax(
+                "only `bits 16` is supported".to_string()
+            ))
+        );
+    }
+}
@@ -2,7 +2,9 @@ use std::ops::AddAssign;
 
 use crate::{
     disasm::Instruction,
-    fields::{Data, EffectiveAddress, Operand, Operation, Register, Wide},
+    fields::{EffectiveAddress, Operand, Operation, Register, Wide},
+    instruction::InstructionPrefix,
+    simulator::SimErrorKind,
 };
 
 #[derive(Default)]
@@ -42,66 +44,83 @@ impl Instruction {
         (Clocks8086(c86), Clocks8088(c88))
     }
 
-    pub fn clocks<F>(&self, is_ea_odd: F) -> (Clocks8086, Clocks8088)
+    /// Memory operand clocks: `base` plus the EA time, with word transfer penalties.
+    fn get_clocks_for_ea<F>(
+        &self,
+        base: usize,
+        transfers: usize,
+        ea: EffectiveAddress,
+        is_ea_odd: &F,
+    ) -> Result<(Clocks8086, Clocks8088), SimErrorKind>
+    where
+        F: Fn(EffectiveAddress) -> bool,
+    {
+        let base = base + ea.clocks();
+        match ea.wide() {
+            Wide::Word => Ok(self.get_clocks_for_wide(base, transfers, is_ea_odd(ea))),
+            Wide::Byte => Ok((Clocks8086(base), Clocks8088(base))),
+            _ => Err(SimErrorKind::invalid_operands(self)),
+        }
+    }
+
+    pub fn clocks<F>(&self, is_ea_odd: F) -> Result<(Clocks8086, Clocks8088), SimErrorKind>
     where
         F: Fn(EffectiveAddress) -> bool,
     {
-        match self.operation {
-            Operation::Add => {
-                let first = self.first.expect("first operand exist for Add op");
-                let second = self.second.expect("second operand exist for Add op");
+        Ok(match self.operation {
+            // ADC, SUB and SBB take as long as ADD
+            Operation::Add | Operation::ADC | Operation::Sub | Operation::SBB => {
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let second = self
+                    .second
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match (first, second) {
                     (Operand::Register(_), Operand::Register(_)) => (Clocks8086(3), Clocks8088(3)),
                     (Operand::Register(_), Operand::EffectiveAddress(ea)) => {
-                        let base = 9 + ea.clocks();
-                        match ea.wide() {
-                            Wide::Word => self.get_clocks_for_wide(base, 1, is_ea_odd(ea)),
-                            Wide::Byte => (Clocks8086(base), Clocks8088(base)),
-                            _ => unreachable!(),
-                        }
+                        self.get_clocks_for_ea(9, 1, ea, &is_ea_odd)?
                     }
                     (Operand::EffectiveAddress(ea), Operand::Register(_)) => {
-                        if ea.wide() == Wide::Byte {
-                            unimplemented!()
-                        }
-                        self.get_clocks_for_wide(16 + ea.clocks(), 2, is_ea_odd(ea))
+                        self.get_clocks_for_ea(16, 2, ea, &is_ea_odd)?
                     }
                     (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(4), Clocks8088(4)),
                     (Operand::EffectiveAddress(ea), Operand::Immediate(_)) => {
-                        if ea.wide() == Wide::Byte {
-                            unimplemented!()
-                        }
-                        self.get_clocks_for_wide(17 + ea.clocks(), 2, is_ea_odd(ea))
+                        self.get_clocks_for_ea(17, 2, ea, &is_ea_odd)?
                     }
-                    _ => unreachable!("{:?}", self),
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
             Operation::Mov => {
-                let first = self.first.expect("first operand exist for Mov op");
-                let second = self.second.expect("second operand exist for Mov op");
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let second = self
+                    .second
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match (first, second) {
-                    (Operand::EffectiveAddress(ea), Operand::Register(Register::AX)) => {
-                        self.get_clocks_for_wide(10, 1, is_ea_odd(ea))
-                    }
-                    (Operand::Register(Register::AX), Operand::EffectiveAddress(ea)) => {
-                        self.get_clocks_for_wide(10, 1, is_ea_odd(ea))
-                    }
+                    // the accumulator forms only take a direct address
+                    (
+                        Operand::EffectiveAddress(ea @ EffectiveAddress::DirectAddress(..)),
+                        Operand::Register(Register::AX | Register::AL),
+                    )
+                    | (
+                        Operand::Register(Register::AX | Register::AL),
+                        Operand::EffectiveAddress(ea @ EffectiveAddress::DirectAddress(..)),
+                    ) => match ea.wide() {
+                        Wide::Word => self.get_clocks_for_wide(10, 1, is_ea_odd(ea)),
+                        _ => (Clocks8086(10), Clocks8088(10)),
+                    },
                     (Operand::Register(_), Operand::Register(_)) => (Clocks8086(2), Clocks8088(2)),
                     (Operand::Register(_), Operand::EffectiveAddress(ea)) => {
-                        self.get_clocks_for_wide(8 + ea.clocks(), 1, is_ea_odd(ea))
+                        self.get_clocks_for_ea(8, 1, ea, &is_ea_odd)?
                     }
                     (Operand::EffectiveAddress(ea), Operand::Register(_)) => {
-                        self.get_clocks_for_wide(9 + ea.clocks(), 1, is_ea_odd(ea))
-                    }
-                    (Operand::Register(_), Operand::Immediate(Data::U16(_))) => {
-                        (Clocks8086(4), Clocks8088(4))
-                    }
-                    (Operand::EffectiveAddress(ea), Operand::Immediate(Data::U16(_))) => {
-                        self.get_clocks_for_wide(10 + ea.clocks(), 1, is_ea_odd(ea))
+                        self.get_clocks_for_ea(9, 1, ea, &is_ea_odd)?
                     }
-                    (Operand::EffectiveAddress(ea), Operand::Immediate(Data::U8(_))) => {
-                        let clocks = 10 + ea.clocks();
-                        (Clocks8086(clocks), Clocks8088(clocks))
+                    (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(4), Clocks8088(4)),
+                    (Operand::EffectiveAddress(ea), Operand::Immediate(_)) => {
+                        self.get_clocks_for_ea(10, 1, ea, &is_ea_odd)?
                     }
                     (Operand::SR(_), Operand::Register(_)) => (Clocks8086(2), Clocks8088(2)),
                     (Operand::SR(_), Operand::EffectiveAddress(ea)) => {
@@ -111,64 +130,338 @@ impl Instruction {
                     (Operand::EffectiveAddress(ea), Operand::SR(_)) => {
                         self.get_clocks_for_wide(9 + ea.clocks(), 1, is_ea_odd(ea))
                     }
-                    _ => unimplemented!("{:?}", self),
+                    _ => return Err(SimErrorKind::unsupported(self)),
                 }
             }
-            Operation::TEST => {
-                let first = self.first.expect("first operand exist for Test op");
-                let second = self.second.expect("second operand exist for Test op");
+            Operation::AND | Operation::OR | Operation::XOR => {
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let second = self
+                    .second
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match (first, second) {
                     (Operand::Register(_), Operand::Register(_)) => (Clocks8086(3), Clocks8088(3)),
-                    _ => unimplemented!("{:?}", self),
+                    (Operand::Register(_), Operand::EffectiveAddress(ea)) => {
+                        self.get_clocks_for_ea(9, 1, ea, &is_ea_odd)?
+                    }
+                    (Operand::EffectiveAddress(ea), Operand::Register(_)) => {
+                        self.get_clocks_for_ea(16, 2, ea, &is_ea_odd)?
+                    }
+                    (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(4), Clocks8088(4)),
+                    (Operand::EffectiveAddress(ea), Operand::Immediate(_)) => {
+                        self.get_clocks_for_ea(17, 2, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
-            Operation::XOR => {
-                let first = self.first.expect("first operand exist for Xor op");
-                let second = self.second.expect("second operand exist for Xor op");
+            Operation::TEST => {
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let second = self
+                    .second
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match (first, second) {
                     (Operand::Register(_), Operand::Register(_)) => (Clocks8086(3), Clocks8088(3)),
-                    _ => unimplemented!("{:?}", self),
+                    (Operand::Register(_), Operand::EffectiveAddress(ea))
+                    | (Operand::EffectiveAddress(ea), Operand::Register(_)) => {
+                        self.get_clocks_for_ea(9, 1, ea, &is_ea_odd)?
+                    }
+                    (Operand::Register(Register::AX | Register::AL), Operand::Immediate(_)) => {
+                        (Clocks8086(4), Clocks8088(4))
+                    }
+                    (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(5), Clocks8088(5)),
+                    (Operand::EffectiveAddress(ea), Operand::Immediate(_)) => {
+                        self.get_clocks_for_ea(11, 1, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
+            Operation::NOT => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Register(_) => (Clocks8086(3), Clocks8088(3)),
+                Operand::EffectiveAddress(ea) => self.get_clocks_for_ea(16, 2, ea, &is_ea_odd)?,
+                _ => return Err(SimErrorKind::invalid_operands(self)),
+            },
             Operation::INC => {
-                let first = self.first.expect("first operand exist for Inc op");
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match first {
                     Operand::Register(reg) => {
                         let clocks = if reg.is_wide() { 2 } else { 3 };
                         (Clocks8086(clocks), Clocks8088(clocks))
                     }
-                    _ => unimplemented!("{:?}", self),
+                    Operand::EffectiveAddress(ea) => {
+                        self.get_clocks_for_ea(15, 2, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
             Operation::DEC => {
-                let first = self.first.expect("first operand exist for Dec op");
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match first {
                     Operand::Register(reg) => {
                         let clocks = if reg.is_wide() { 2 } else { 3 };
                         (Clocks8086(clocks), Clocks8088(clocks))
                     }
-                    _ => unimplemented!("{:?}", self),
+                    Operand::EffectiveAddress(ea) => {
+                        self.get_clocks_for_ea(15, 2, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
             Operation::Cmp => {
-                let first = self.first.expect("first operand exist for Cmp op");
-                let second = self.second.expect("second operand exist for Cmp op");
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let second = self
+                    .second
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
                 match (first, second) {
                     (Operand::Register(_), Operand::Register(_)) => (Clocks8086(3), Clocks8088(3)),
+                    (Operand::Register(_), Operand::EffectiveAddress(ea))
+                    | (Operand::EffectiveAddress(ea), Operand::Register(_)) => {
+                        self.get_clocks_for_ea(9, 1, ea, &is_ea_odd)?
+                    }
                     (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(4), Clocks8088(4)),
-                    _ => unimplemented!("{:?}", self),
+                    (Operand::EffectiveAddress(ea), Operand::Immediate(_)) => {
+                        self.get_clocks_for_ea(10, 1, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
                 }
             }
-            Operation::Ret => (Clocks8086(8), Clocks8088(8)),
-            Operation::SHR => {
-                let first = self.first.expect("first operand exist for Shr op");
-                let second = self.second.expect("second operand exist for Shr op");
-                match (first, second) {
-                    (Operand::Register(_), Operand::Immediate(_)) => (Clocks8086(2), Clocks8088(2)),
-                    _ => unimplemented!("{:?}", self),
+            Operation::NEG => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Register(_) => (Clocks8086(3), Clocks8088(3)),
+                Operand::EffectiveAddress(ea) => self.get_clocks_for_ea(16, 2, ea, &is_ea_odd)?,
+                _ => return Err(SimErrorKind::invalid_operands(self)),
+            },
+            // lowest of the operand dependent ranges given in the manual
+            Operation::MUL | Operation::IMUL | Operation::DIV | Operation::IDIV => {
+                let (reg8, reg16) = match self.operation {
+                    Operation::MUL => (70, 118),
+                    Operation::IMUL => (80, 128),
+                    Operation::DIV => (80, 144),
+                    _ => (101, 165),
+                };
+                match self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+                {
+                    Operand::Register(reg) if reg.is_wide() => {
+                        (Clocks8086(reg16), Clocks8088(reg16))
+                    }
+                    Operand::Register(_) => (Clocks8086(reg8), Clocks8088(reg8)),
+                    Operand::EffectiveAddress(ea) => match ea.wide() {
+                        Wide::Word => {
+                            self.get_clocks_for_wide(reg16 + 6 + ea.clocks(), 1, is_ea_odd(ea))
+                        }
+                        _ => (
+                            Clocks8086(reg8 + 6 + ea.clocks()),
+                            Clocks8088(reg8 + 6 + ea.clocks()),
+                        ),
+                    },
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
+                }
+            }
+            Operation::HLT
+            | Operation::CLC
+            | Operation::STC
+            | Operation::CMC
+            | Operation::CLD
+            | Operation::STD
+            | Operation::CLI
+            | Operation::STI => (Clocks8086(2), Clocks8088(2)),
+            Operation::LAHF | Operation::SAHF => (Clocks8086(4), Clocks8088(4)),
+            Operation::INT => self.get_clocks_for_wide(51, 5, false),
+            Operation::INT3 => self.get_clocks_for_wide(52, 5, false),
+            // when no interrupt is taken, which is the usual case
+            Operation::INTO => (Clocks8086(4), Clocks8088(4)),
+            Operation::IRET => self.get_clocks_for_wide(24, 3, false),
+            Operation::IN | Operation::OUT => {
+                let (acc, port) = match self.operation {
+                    Operation::IN => (self.first, self.second),
+                    _ => (self.second, self.first),
+                };
+                let base = match port {
+                    Some(Operand::Register(Register::DX)) => 8,
+                    _ => 10,
+                };
+                match acc {
+                    Some(Operand::Register(Register::AX)) => {
+                        self.get_clocks_for_wide(base, 1, false)
+                    }
+                    _ => (Clocks8086(base), Clocks8088(base)),
+                }
+            }
+            Operation::PUSHF => self.get_clocks_for_wide(10, 1, false),
+            Operation::POPF => self.get_clocks_for_wide(8, 1, false),
+            Operation::Push => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Register(_) => self.get_clocks_for_wide(11, 1, false),
+                Operand::SR(_) => self.get_clocks_for_wide(10, 1, false),
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(16 + ea.clocks(), 2, is_ea_odd(ea))
+                }
+                _ => return Err(SimErrorKind::unsupported(self)),
+            },
+            Operation::Pop => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Register(_) | Operand::SR(_) => self.get_clocks_for_wide(8, 1, false),
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(17 + ea.clocks(), 2, is_ea_odd(ea))
+                }
+                _ => return Err(SimErrorKind::unsupported(self)),
+            },
+            Operation::Call => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Increment(_) => self.get_clocks_for_wide(19, 1, false),
+                Operand::Register(_) => self.get_clocks_for_wide(16, 1, false),
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(21 + ea.clocks(), 2, is_ea_odd(ea))
+                }
+                Operand::CsIp(_) => self.get_clocks_for_wide(28, 2, false),
+                _ => return Err(SimErrorKind::unsupported(self)),
+            },
+            Operation::Jmp => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::Increment(_) | Operand::CsIp(_) => (Clocks8086(15), Clocks8088(15)),
+                Operand::Register(_) => (Clocks8086(11), Clocks8088(11)),
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(18 + ea.clocks(), 1, is_ea_odd(ea))
+                }
+                _ => return Err(SimErrorKind::invalid_operands(self)),
+            },
+            Operation::JmpFar => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(24 + ea.clocks(), 2, is_ea_odd(ea))
+                }
+                _ => return Err(SimErrorKind::invalid_operands(self)),
+            },
+            Operation::CallFar => match self
+                .first
+                .ok_or_else(|| SimErrorKind::invalid_operands(self))?
+            {
+                Operand::EffectiveAddress(ea) => {
+                    self.get_clocks_for_wide(37 + ea.clocks(), 4, is_ea_odd(ea))
+                }
+                _ => return Err(SimErrorKind::unsupported(self)),
+            },
+            Operation::Ret => match self.first {
+                None => (Clocks8086(8), Clocks8088(8)),
+                Some(_) => self.get_clocks_for_wide(12, 1, false),
+            },
+            Operation::RetFar => match self.first {
+                None => self.get_clocks_for_wide(18, 2, false),
+                Some(_) => self.get_clocks_for_wide(17, 2, false),
+            },
+            // CL counts also take `clocks_per_bit` for each bit shifted
+            Operation::SHL
+            | Operation::SHR
+            | Operation::SAR
+            | Operation::ROL
+            | Operation::ROR
+            | Operation::RCL
+            | Operation::RCR => {
+                let first = self
+                    .first
+                    .ok_or_else(|| SimErrorKind::invalid_operands(self))?;
+                let by_cl = self.second == Some(Operand::Register(Register::CL));
+                match first {
+                    Operand::Register(_) if by_cl => (Clocks8086(8), Clocks8088(8)),
+                    Operand::Register(_) => (Clocks8086(2), Clocks8088(2)),
+                    Operand::EffectiveAddress(ea) => {
+                        self.get_clocks_for_ea(if by_cl { 20 } else { 15 }, 2, ea, &is_ea_odd)?
+                    }
+                    _ => return Err(SimErrorKind::invalid_operands(self)),
+                }
+            }
+            // a repeated string instruction takes `clocks_per_rep` for each iteration on top
+            op if op.is_string() => {
+                let (single, _, transfers) = self.string_clocks();
+                if self.is_repeated_string() {
+                    (Clocks8086(9), Clocks8088(9))
+                } else {
+                    self.get_clocks_for_wide(single, transfers, false)
                 }
             }
-            _ => unimplemented!("{:?}", self),
+            _ => return Err(SimErrorKind::unsupported(self)),
+        })
+    }
+
+    fn is_repeated_string(&self) -> bool {
+        self.operation.is_string()
+            && matches!(
+                self.prefix,
+                Some(InstructionPrefix::RepE | InstructionPrefix::RepNE)
+            )
+    }
+
+    /// Clocks of a string instruction on its own and per repetition, and the memory transfers
+    /// of a word sized iteration
+    fn string_clocks(&self) -> (usize, usize, usize) {
+        let (single, per_rep, transfers) = match self.operation {
+            Operation::MOVSB | Operation::MOVSW => (18, 17, 2),
+            Operation::CMPSB | Operation::CMPSW => (22, 22, 2),
+            Operation::SCASB | Operation::SCASW => (15, 15, 1),
+            Operation::LODSB | Operation::LODSW => (12, 13, 1),
+            _ => (11, 10, 1),
+        };
+        let word = matches!(
+            self.operation,
+            Operation::MOVSW
+                | Operation::CMPSW
+                | Operation::SCASW
+                | Operation::LODSW
+                | Operation::STOSW
+        );
+        (single, per_rep, if word { transfers } else { 0 })
+    }
+
+    /// Clocks for each iteration of a repeated string instruction
+    pub fn clocks_per_rep(&self) -> (Clocks8086, Clocks8088) {
+        if !self.is_repeated_string() {
+            return (Clocks8086(0), Clocks8088(0));
+        }
+        let (_, per_rep, transfers) = self.string_clocks();
+        self.get_clocks_for_wide(per_rep, transfers, false)
+    }
+
+    /// Extra clocks for each bit of a shift or rotate by CL
+    pub fn clocks_per_bit(&self) -> usize {
+        let is_shift = matches!(
+            self.operation,
+            Operation::SHL
+                | Operation::SHR
+                | Operation::SAR
+                | Operation::ROL
+                | Operation::ROR
+                | Operation::RCL
+                | Operation::RCR
+        );
+        if is_shift && self.second == Some(Operand::Register(Register::CL)) {
+            4
+        } else {
+            0
         }
     }
 }
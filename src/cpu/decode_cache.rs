@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use crate::disasm::Instruction;
+
+/// Instructions already decoded from memory, by address, so that loops don't decode the same
+/// bytes over and over
+#[derive(Default)]
+pub struct DecodeCache {
+    instructions: HashMap<u32, Instruction>,
+    /// Size of the longest cached instruction, 6 bytes unless it carries prefixes
+    longest: u32,
+}
+
+impl DecodeCache {
+    pub fn get(&self, addr: u32) -> Option<&Instruction> {
+        self.instructions.get(&addr)
+    }
+
+    pub fn insert(&mut self, addr: u32, inst: Instruction) {
+        self.longest = self.longest.max(inst.size as u32);
+        self.instructions.insert(addr, inst);
+    }
+
+    /// Drops every instruction the written byte is part of. Only instructions starting at most
+    /// `longest - 1` bytes before it can cover it.
+    pub fn invalidate(&mut self, addr: u32) {
+        let first = addr.saturating_sub(self.longest.saturating_sub(1));
+        for start in first..=addr {
+            if self
+                .instructions
+                .get(&start)
+                .is_some_and(|inst| addr < start + inst.size as u32)
+            {
+                self.instructions.remove(&start);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fields::{Operation, Register};
+
+    use super::*;
+
+    fn inst(size: usize) -> Instruction {
+        Instruction {
+            operation: Operation::INC,
+            first: Some(Register::AX.into()),
+            second: None,
+            prefix: None,
+            size,
+        }
+    }
+
+    #[test]
+    fn invalidate_drops_covering_instructions() {
+        let mut cache = DecodeCache::default();
+        cache.insert(0x100, inst(6));
+        cache.insert(0x106, inst(1));
+        cache.insert(0x107, inst(2));
+
+        cache.invalidate(0x105);
+        assert!(cache.get(0x100).is_none());
+        assert!(cache.get(0x106).is_some());
+
+        cache.invalidate(0x108);
+        assert!(cache.get(0x106).is_some());
+        assert!(cache.get(0x107).is_none());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    disasm::Instruction,
+    fields::{Operand, Operation, Register},
+};
+
+use super::Flags;
+
+/// What an instruction does to a single flag, as listed in the 8086 manual
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagEffect {
+    /// Set from the result
+    Affected,
+    Cleared,
+    Set,
+    /// Left in a state the manual doesn't specify, see `UndefinedFlags`
+    Undefined,
+    Unchanged,
+}
+
+/// Effects of an instruction on the status flags OF, SF, ZF, AF, PF and CF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagEffects {
+    pub overflow: FlagEffect,
+    pub sign: FlagEffect,
+    pub zero: FlagEffect,
+    pub auxiliary: FlagEffect,
+    pub parity: FlagEffect,
+    pub carry: FlagEffect,
+}
+
+/// What to do with flags an instruction leaves undefined
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UndefinedFlags {
+    /// Keep the value from before the instruction
+    Leave,
+    Clear,
+    /// Keep what the handler derived from the result, close to what the 8086 leaves behind
+    #[default]
+    Silicon,
+}
+
+impl FlagEffects {
+    const fn all(effect: FlagEffect) -> Self {
+        Self {
+            overflow: effect,
+            sign: effect,
+            zero: effect,
+            auxiliary: effect,
+            parity: effect,
+            carry: effect,
+        }
+    }
+}
+
+impl Instruction {
+    /// Flag effects of the instruction. `cl` gives the count of shifts and rotates by CL.
+    pub fn flag_effects(&self, cl: u8) -> FlagEffects {
+        use FlagEffect::*;
+
+        let count = match self.second {
+            Some(Operand::Register(Register::CL)) => cl,
+            _ => 1,
+        };
+        // the manual only defines OF for single bit shifts
+        let shift_overflow = if count == 1 { Affected } else { Undefined };
+
+        match self.operation {
+            Operation::Add
+            | Operation::ADC
+            | Operation::Sub
+            | Operation::SBB
+            | Operation::Cmp
+            | Operation::NEG
+            | Operation::CMPSB
+            | Operation::CMPSW
+            | Operation::SCASB
+            | Operation::SCASW => FlagEffects::all(Affected),
+            Operation::INC | Operation::DEC => FlagEffects {
+                carry: Unchanged,
+                ..FlagEffects::all(Affected)
+            },
+            Operation::AND | Operation::OR | Operation::XOR | Operation::TEST => FlagEffects {
+                overflow: Cleared,
+                auxiliary: Undefined,
+                carry: Cleared,
+                ..FlagEffects::all(Affected)
+            },
+            Operation::MUL | Operation::IMUL => FlagEffects {
+                overflow: Affected,
+                carry: Affected,
+                ..FlagEffects::all(Undefined)
+            },
+            Operation::DIV | Operation::IDIV => FlagEffects::all(Undefined),
+            Operation::SHL
+            | Operation::SHR
+            | Operation::SAR
+            | Operation::ROL
+            | Operation::ROR
+            | Operation::RCL
+            | Operation::RCR
+                if count == 0 =>
+            {
+                FlagEffects::all(Unchanged)
+            }
+            Operation::SHL | Operation::SHR | Operation::SAR => FlagEffects {
+                overflow: shift_overflow,
+                auxiliary: Undefined,
+                ..FlagEffects::all(Affected)
+            },
+            Operation::ROL | Operation::ROR | Operation::RCL | Operation::RCR => FlagEffects {
+                overflow: shift_overflow,
+                carry: Affected,
+                ..FlagEffects::all(Unchanged)
+            },
+            Operation::POPF | Operation::IRET | Operation::CMC => FlagEffects::all(Affected),
+            Operation::SAHF => FlagEffects {
+                overflow: Unchanged,
+                ..FlagEffects::all(Affected)
+            },
+            Operation::CLC => FlagEffects {
+                carry: Cleared,
+                ..FlagEffects::all(Unchanged)
+            },
+            Operation::STC => FlagEffects {
+                carry: Set,
+                ..FlagEffects::all(Unchanged)
+            },
+            _ => FlagEffects::all(Unchanged),
+        }
+    }
+}
+
+impl Flags {
+    /// Combines the flags from before an instruction with those its handler computed
+    pub fn apply(
+        before: Flags,
+        computed: Flags,
+        effects: FlagEffects,
+        undefined: UndefinedFlags,
+    ) -> Flags {
+        let pick = |effect, before, computed| match (effect, undefined) {
+            (FlagEffect::Affected, _) | (FlagEffect::Undefined, UndefinedFlags::Silicon) => {
+                computed
+            }
+            (FlagEffect::Cleared, _) | (FlagEffect::Undefined, UndefinedFlags::Clear) => false,
+            (FlagEffect::Set, _) => true,
+            (FlagEffect::Unchanged, _) | (FlagEffect::Undefined, UndefinedFlags::Leave) => before,
+        };
+        Flags {
+            overflow: pick(effects.overflow, before.overflow, computed.overflow),
+            sign: pick(effects.sign, before.sign, computed.sign),
+            zero: pick(effects.zero, before.zero, computed.zero),
+            auxiliary: pick(effects.auxiliary, before.auxiliary, computed.auxiliary),
+            parity: pick(effects.parity, before.parity, computed.parity),
+            carry: pick(effects.carry, before.carry, computed.carry),
+            // control flags are only ever set on purpose
+            ..computed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fields::Data;
+
+    use super::*;
+
+    fn inst(operation: Operation, first: Operand, second: Option<Operand>) -> Instruction {
+        Instruction {
+            operation,
+            first: Some(first),
+            second,
+            prefix: None,
+            size: 2,
+        }
+    }
+
+    #[test]
+    fn inc_leaves_carry_alone() {
+        let effects = inst(Operation::INC, Register::AX.into(), None).flag_effects(0);
+        let before = Flags {
+            carry: true,
+            ..Flags::default()
+        };
+        let computed = Flags {
+            zero: true,
+            ..Flags::default()
+        };
+        let after = Flags::apply(before, computed, effects, UndefinedFlags::Silicon);
+        assert!(after.carry && after.zero);
+    }
+
+    #[test]
+    fn undefined_flags_follow_policy() {
+        let and = inst(
+            Operation::AND,
+            Register::AL.into(),
+            Some(Data::U8(1).into()),
+        );
+        let before = Flags {
+            auxiliary: true,
+            ..Flags::default()
+        };
+        let apply = |computed_af, policy| {
+            let computed = Flags {
+                auxiliary: computed_af,
+                ..Flags::default()
+            };
+            Flags::apply(before, computed, and.flag_effects(0), policy).auxiliary
+        };
+        assert!(apply(false, UndefinedFlags::Leave));
+        assert!(!apply(true, UndefinedFlags::Clear));
+        assert!(apply(true, UndefinedFlags::Silicon));
+    }
+
+    #[test]
+    fn shift_count_decides_overflow() {
+        let shl = inst(
+            Operation::SHL,
+            Register::AX.into(),
+            Some(Register::CL.into()),
+        );
+        assert_eq!(shl.flag_effects(1).overflow, FlagEffect::Affected);
+        assert_eq!(shl.flag_effects(3).overflow, FlagEffect::Undefined);
+        assert_eq!(shl.flag_effects(0), FlagEffects::all(FlagEffect::Unchanged));
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
     handlers::ArithmeticOp,
 };
 
-#[derive(Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 pub struct Flags {
     pub zero: bool,
     pub sign: bool,
@@ -13,11 +13,20 @@ pub struct Flags {
     pub carry: bool,
     pub overflow: bool,
     pub auxiliary: bool,
+    pub direction: bool,
+    pub interrupt: bool,
+    pub trap: bool,
 }
 
+/// Bits 12-15 and bit 1 of the 8086 FLAGS register always read as set
+const FIXED_BITS: u16 = 0xf002;
+
 macro_rules! generate_flag_checks {
     ($($field:ident => $letter:expr),*) => {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            if f.alternate() {
+                return writeln!(f, "   flags: {:#06x} {}", self.to_u16(), self.letters());
+            }
             let mut print_header = true;
             $(
                 if self.$field {
@@ -39,14 +48,68 @@ impl Display for Flags {
     generate_flag_checks!(
         auxiliary => "A",
         carry => "C",
+        direction => "D",
+        interrupt => "I",
         overflow => "O",
         parity => "P",
         sign => "S",
+        trap => "T",
         zero => "Z"
     );
 }
 
 impl Flags {
+    /// Letters of the flags that are set, in FLAGS register bit order like the reference trace
+    pub fn letters(&self) -> String {
+        [
+            (self.carry, 'C'),
+            (self.parity, 'P'),
+            (self.auxiliary, 'A'),
+            (self.zero, 'Z'),
+            (self.sign, 'S'),
+            (self.trap, 'T'),
+            (self.interrupt, 'I'),
+            (self.direction, 'D'),
+            (self.overflow, 'O'),
+        ]
+        .into_iter()
+        .filter_map(|(set, letter)| set.then_some(letter))
+        .collect()
+    }
+
+    /// The FLAGS register as PUSHF stores it
+    pub fn to_u16(&self) -> u16 {
+        [
+            (self.carry, 0),
+            (self.parity, 2),
+            (self.auxiliary, 4),
+            (self.zero, 6),
+            (self.sign, 7),
+            (self.trap, 8),
+            (self.interrupt, 9),
+            (self.direction, 10),
+            (self.overflow, 11),
+        ]
+        .into_iter()
+        .fold(FIXED_BITS, |word, (set, bit)| word | (set as u16) << bit)
+    }
+
+    /// Flags from a FLAGS register image, as POPF loads it. The fixed bits are ignored.
+    pub fn from_u16(word: u16) -> Self {
+        let bit = |n: u16| word & (1 << n) != 0;
+        Self {
+            carry: bit(0),
+            parity: bit(2),
+            auxiliary: bit(4),
+            zero: bit(6),
+            sign: bit(7),
+            trap: bit(8),
+            interrupt: bit(9),
+            direction: bit(10),
+            overflow: bit(11),
+        }
+    }
+
     pub fn set(&mut self, lhs: Data, rhs: Data, op: ArithmeticOp, computation: DataWithCarry) {
         let DataWithCarry(value, Carry(carry), HalfCarry(half_carry)) = computation;
         self.zero = value.is_zero();
@@ -58,8 +121,13 @@ impl Flags {
             (lhs.is_signed(), rhs.is_signed(), op, value.is_signed()),
             (false, false, ArithmeticOp::Add, true)
                 | (true, true, ArithmeticOp::Add, false)
-                | (true, false, ArithmeticOp::Sub | ArithmeticOp::Cmp, false)
-                | (false, true, ArithmeticOp::Sub | ArithmeticOp::Cmp, true)
+                | (false, false, ArithmeticOp::Adc | ArithmeticOp::Inc, true)
+                | (true, true, ArithmeticOp::Adc, false)
+                | (true, false, ArithmeticOp::Sub | ArithmeticOp::Sbb | ArithmeticOp::Cmp, false)
+                | (true, false, ArithmeticOp::Dec, false)
+                | (false, true, ArithmeticOp::Sub | ArithmeticOp::Sbb | ArithmeticOp::Cmp, true)
+                // only negating the most negative value overflows, giving itself back
+                | (true, _, ArithmeticOp::Neg, true)
         );
 
         self.auxiliary = half_carry;
@@ -74,3 +142,24 @@ impl Flags {
         self.auxiliary = false;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_word_round_trips() {
+        let flags = Flags {
+            carry: true,
+            zero: true,
+            direction: true,
+            overflow: true,
+            ..Flags::default()
+        };
+        assert_eq!(flags.to_u16(), 0xfc43);
+        assert_eq!(Flags::from_u16(flags.to_u16()), flags);
+        assert_eq!(Flags::default().to_u16(), FIXED_BITS);
+        assert_eq!(flags.letters(), "CZDO");
+        assert_eq!(format!("{:#}", flags), "   flags: 0xfc43 CZDO\n");
+    }
+}
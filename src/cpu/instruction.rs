@@ -1,38 +1,40 @@
-use crate::{disasm::Instruction, fields::Operation};
+use crate::{disasm::Instruction, fields::Operation, simulator::SimErrorKind};
 
 pub struct JmpTakenClocks(pub usize);
 pub struct JmpNotTakenClocks(pub usize);
 
 impl Instruction {
+    /// Conditional jumps, JCXZ and the LOOPs, whose clocks depend on whether they're taken
     pub fn is_conditional_advance(&self) -> bool {
-        matches!(
-            self.operation,
-            Operation::JE
-                | Operation::JL
-                | Operation::JLE
-                | Operation::JB
-                | Operation::JBE
-                | Operation::JP
-                | Operation::JO
-                | Operation::JS
-                | Operation::JNE
-                | Operation::JNL
-                | Operation::JNLE
-                | Operation::JNB
-                | Operation::JNBE
-                | Operation::JNP
-                | Operation::JNO
-                | Operation::JNS
-        )
+        self.clocks_for_coditional_advance().is_ok()
     }
 
-    pub fn clocks_for_coditional_advance(&self) -> (JmpTakenClocks, JmpNotTakenClocks) {
-        assert!(self.is_conditional_advance());
-        match self.operation {
-            Operation::JE => (JmpTakenClocks(16), JmpNotTakenClocks(4)),
-            Operation::JNE => (JmpTakenClocks(16), JmpNotTakenClocks(4)),
-            Operation::JB => (JmpTakenClocks(16), JmpNotTakenClocks(4)),
-            _ => unimplemented!("{:?}", self),
-        }
+    pub fn clocks_for_coditional_advance(
+        &self,
+    ) -> Result<(JmpTakenClocks, JmpNotTakenClocks), SimErrorKind> {
+        let (taken, not_taken) = match self.operation {
+            Operation::JE
+            | Operation::JL
+            | Operation::JLE
+            | Operation::JB
+            | Operation::JBE
+            | Operation::JP
+            | Operation::JO
+            | Operation::JS
+            | Operation::JNE
+            | Operation::JNL
+            | Operation::JNLE
+            | Operation::JNB
+            | Operation::JNBE
+            | Operation::JNP
+            | Operation::JNO
+            | Operation::JNS => (16, 4),
+            Operation::JCXZ => (18, 6),
+            Operation::LOOP => (17, 5),
+            Operation::LOOPZ => (18, 6),
+            Operation::LOOPNZ => (19, 5),
+            _ => return Err(SimErrorKind::unsupported(self)),
+        };
+        Ok((JmpTakenClocks(taken), JmpNotTakenClocks(not_taken)))
     }
 }
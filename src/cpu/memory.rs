@@ -1,33 +1,135 @@
-pub struct Memory([u8; 1024 * 64]);
+use crate::fields::{Data, Wide};
+
+/// Size of the 8086's 20-bit physical address space
+pub const MEMORY_SIZE: usize = 1 << 20;
+
+/// A segment:offset pair, as the program sees memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub segment: u16,
+    pub offset: u16,
+}
+
+impl Address {
+    pub fn new(segment: u16, offset: u16) -> Self {
+        Self { segment, offset }
+    }
+
+    /// 20-bit physical address, wrapping around at 1 MiB like the 8086 does
+    pub fn physical(self) -> u32 {
+        (((self.segment as u32) << 4) + self.offset as u32) & (MEMORY_SIZE as u32 - 1)
+    }
+
+    /// Address `n` bytes further on, wrapping within the segment
+    pub fn offset_by(self, n: u16) -> Self {
+        Self::new(self.segment, self.offset.wrapping_add(n))
+    }
+}
+
+/// A byte stored to memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    /// physical address
+    pub address: u32,
+    pub old: u8,
+    pub new: u8,
+}
+
+pub struct Memory {
+    bytes: Box<[u8]>,
+    /// bytes stored since the last call to `take_writes`
+    writes: Vec<MemoryWrite>,
+}
 
 impl Default for Memory {
     fn default() -> Self {
-        Self([0u8; 1024 * 64])
+        Self {
+            bytes: vec![0u8; MEMORY_SIZE].into_boxed_slice(),
+            writes: Vec::new(),
+        }
     }
 }
 
 impl Memory {
     pub fn raw(&self) -> &[u8] {
-        &self.0
+        &self.bytes
+    }
+
+    /// Bytes from `addr` to the end of physical memory
+    pub fn slice_from(&self, addr: Address) -> &[u8] {
+        &self.bytes[addr.physical() as usize..]
+    }
+
+    /// Copies `data` in starting at `addr`. The bytes are recorded as writes, so that decodes
+    /// cached from the old contents get dropped.
+    pub fn load(&mut self, addr: Address, data: &[u8]) {
+        for (i, &byte) in data.iter().enumerate() {
+            self.store_8(addr.offset_by(i as u16), byte);
+        }
+    }
+
+    pub fn take_writes(&mut self) -> Vec<MemoryWrite> {
+        std::mem::take(&mut self.writes)
     }
 
-    pub fn load_16(&self, addr: u16) -> u16 {
-        let low = *self.0.get(addr as usize).expect("addr in range");
-        let high = *self.0.get(addr as usize + 1).expect("addr in range");
-        u16::from_le_bytes([low, high])
+    pub fn load_16(&self, addr: Address) -> u16 {
+        u16::from_le_bytes([self.load_8(addr), self.load_8(addr.offset_by(1))])
     }
 
-    pub fn load_8(&self, addr: u16) -> u8 {
-        *self.0.get(addr as usize).expect("addr in range")
+    pub fn load_8(&self, addr: Address) -> u8 {
+        self.bytes[addr.physical() as usize]
     }
 
-    pub fn store_16(&mut self, addr: u16, val: u16) {
+    pub fn store_16(&mut self, addr: Address, val: u16) {
         let [low, high] = val.to_le_bytes();
-        *self.0.get_mut(addr as usize).expect("addr in range") = low;
-        *self.0.get_mut(addr as usize + 1).expect("addr in range") = high;
+        self.store_8(addr, low);
+        self.store_8(addr.offset_by(1), high);
+    }
+
+    /// Loads a byte or a word, as given by `wide`. `Wide::None` has no width to load.
+    pub fn load_data(&self, addr: Address, wide: Wide) -> Option<Data> {
+        match wide {
+            Wide::Byte => Some(Data::U8(self.load_8(addr))),
+            Wide::Word => Some(Data::U16(self.load_16(addr))),
+            Wide::None => None,
+        }
+    }
+
+    pub fn store_data(&mut self, addr: Address, val: Data) {
+        match val {
+            Data::U8(val) => self.store_8(addr, val),
+            Data::U16(val) => self.store_16(addr, val),
+        }
+    }
+
+    pub fn store_8(&mut self, addr: Address, val: u8) {
+        let address = addr.physical();
+        let old = std::mem::replace(&mut self.bytes[address as usize], val);
+        self.writes.push(MemoryWrite {
+            address,
+            old,
+            new: val,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_and_offset_make_physical_address() {
+        assert_eq!(Address::new(0x1234, 0x0010).physical(), 0x12350);
+        assert_eq!(Address::new(0xffff, 0x0010).physical(), 0x00000);
     }
 
-    pub fn store_8(&mut self, addr: u16, val: u8) {
-        *self.0.get_mut(addr as usize).expect("addr in range") = val;
+    #[test]
+    fn word_access_wraps_within_segment() {
+        let mut memory = Memory::default();
+        let addr = Address::new(0x1000, 0xffff);
+        memory.store_16(addr, 0xbeef);
+        assert_eq!(memory.raw()[0x1ffff], 0xef);
+        assert_eq!(memory.raw()[0x10000], 0xbe);
+        assert_eq!(memory.load_16(addr), 0xbeef);
     }
 }
@@ -1,10 +1,14 @@
 mod clocks;
+mod decode_cache;
+mod flag_effects;
 mod flags;
 mod instruction;
 mod memory;
 mod registers;
 
 pub use clocks::*;
+pub use decode_cache::*;
+pub use flag_effects::*;
 pub use flags::*;
 pub use instruction::*;
 pub use memory::*;
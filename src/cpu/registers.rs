@@ -1,8 +1,9 @@
 use std::fmt::Display;
 
+use super::Address;
 use crate::fields::{Data, EffectiveAddress, Register, SegmentRegister};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Registers {
     ax: u16,
     bx: u16,
@@ -46,14 +47,19 @@ impl GetSetBytes for u16 {
 }
 
 impl Registers {
+    /// LOOP decrements before testing, so CX = 0 wraps round to 0xffff like on hardware
     pub fn dec_cx(&mut self) {
-        self.cx = self.cx.checked_sub(1).unwrap();
+        self.cx = self.cx.wrapping_sub(1);
     }
 
     pub fn cx(&self) -> u16 {
         self.cx
     }
 
+    pub fn cs(&self) -> u16 {
+        self.cs
+    }
+
     pub fn set_reg(&mut self, to: Register, from: Register) {
         let imd = self.get(from);
         self.set_imd(to, imd);
@@ -70,12 +76,7 @@ impl Registers {
     }
 
     pub fn get_sr(&self, sr: SegmentRegister) -> Data {
-        match sr {
-            SegmentRegister::CS => Data::U16(self.cs),
-            SegmentRegister::DS => Data::U16(self.ds),
-            SegmentRegister::ES => Data::U16(self.es),
-            SegmentRegister::SS => Data::U16(self.ss),
-        }
+        Data::U16(self.sr(sr))
     }
 
     pub fn get(&self, reg: Register) -> Data {
@@ -145,21 +146,95 @@ impl Registers {
         }
     }
 
+    /// Offset part of the effective address, wrapping at 64K like the hardware does
     pub fn calculate_eff_addr(&self, ea: EffectiveAddress) -> u16 {
-        match ea {
-            EffectiveAddress::DirectAddress(addr, _) => addr,
-            EffectiveAddress::BX(disp, _) => self.bx + disp.unwrap_or(0),
-            EffectiveAddress::BP_SI(disp, _) => self.bp + self.si + disp.unwrap_or(0),
-            EffectiveAddress::BP(disp, _) => self.bp + disp,
-            EffectiveAddress::SI(disp, _) => self.si + disp.unwrap_or(0),
-            EffectiveAddress::DI(disp, _) => self.di + disp.unwrap_or(0),
-            EffectiveAddress::BP_DI(disp, _) => self.bp + self.di + disp.unwrap_or(0),
-            EffectiveAddress::BX_SI(disp, _) => self.bx + self.si + disp.unwrap_or(0),
-            EffectiveAddress::BX_DI(disp, _) => self.bx + self.di + disp.unwrap_or(0),
+        let (base, disp) = match ea {
+            EffectiveAddress::DirectAddress(addr, _) => (0, addr),
+            EffectiveAddress::BX(disp, _) => (self.bx, disp.unwrap_or(0)),
+            EffectiveAddress::BP_SI(disp, _) => (self.bp.wrapping_add(self.si), disp.unwrap_or(0)),
+            EffectiveAddress::BP(disp, _) => (self.bp, disp),
+            EffectiveAddress::SI(disp, _) => (self.si, disp.unwrap_or(0)),
+            EffectiveAddress::DI(disp, _) => (self.di, disp.unwrap_or(0)),
+            EffectiveAddress::BP_DI(disp, _) => (self.bp.wrapping_add(self.di), disp.unwrap_or(0)),
+            EffectiveAddress::BX_SI(disp, _) => (self.bx.wrapping_add(self.si), disp.unwrap_or(0)),
+            EffectiveAddress::BX_DI(disp, _) => (self.bx.wrapping_add(self.di), disp.unwrap_or(0)),
+        };
+        base.wrapping_add(disp)
+    }
+
+    /// Full segment:offset of the operand, using SS for BP based addressing and DS otherwise,
+    /// unless the instruction carries a segment override
+    pub fn effective_address(
+        &self,
+        ea: EffectiveAddress,
+        segment_override: Option<SegmentRegister>,
+    ) -> Address {
+        let segment = segment_override.unwrap_or(match ea {
+            EffectiveAddress::BP(..)
+            | EffectiveAddress::BP_SI(..)
+            | EffectiveAddress::BP_DI(..) => SegmentRegister::SS,
+            _ => SegmentRegister::DS,
+        });
+        Address::new(self.sr(segment), self.calculate_eff_addr(ea))
+    }
+
+    /// DS:SI, the source of string instructions, which can be overridden
+    pub fn string_source(&self, segment_override: Option<SegmentRegister>) -> Address {
+        let segment = segment_override.unwrap_or(SegmentRegister::DS);
+        Address::new(self.sr(segment), self.si)
+    }
+
+    /// SS:SP
+    pub fn stack_top(&self) -> Address {
+        Address::new(self.ss, self.sp)
+    }
+
+    /// ES:DI, the destination of string instructions, which can't be overridden
+    pub fn string_destination(&self) -> Address {
+        Address::new(self.es, self.di)
+    }
+
+    fn sr(&self, sr: SegmentRegister) -> u16 {
+        match sr {
+            SegmentRegister::CS => self.cs,
+            SegmentRegister::DS => self.ds,
+            SegmentRegister::ES => self.es,
+            SegmentRegister::SS => self.ss,
         }
     }
 }
 
+/// A word register that held `old` before an instruction and `new` after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub name: &'static str,
+    pub old: u16,
+    pub new: u16,
+}
+
+macro_rules! push_if_changed {
+    ($changes:ident, $self:ident, $after:ident, $($field:ident),*) => {
+        $(
+            if $self.$field != $after.$field {
+                $changes.push(RegisterChange {
+                    name: stringify!($field),
+                    old: $self.$field,
+                    new: $after.$field,
+                });
+            }
+        )*
+    };
+}
+
+impl Registers {
+    /// Registers that differ in `after`, in the order they are displayed
+    pub fn changes(&self, after: &Registers) -> Vec<RegisterChange> {
+        let mut changes = Vec::new();
+        push_if_changed!(changes, self, after, ax, bx, cx, dx, sp, bp, si, di, cs, ds, ss, es);
+        changes
+    }
+}
+
 macro_rules! write_if_non_zero {
     ($f:expr, $self:ident, $field:ident) => {
         if $self.$field != 0 {
@@ -196,6 +271,7 @@ impl Display for Registers {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fields::Wide;
 
     #[test]
     fn register_set_16() {
@@ -219,4 +295,25 @@ mod tests {
         regs.set_imd(Register::BH, Data::U8(0));
         assert_eq!(regs.bx, 255)
     }
+
+    #[test]
+    fn effective_address_segments() {
+        let regs = Registers {
+            ds: 0x1000,
+            ss: 0x2000,
+            bp: 0xfffe,
+            ..Default::default()
+        };
+        let ea = EffectiveAddress::BP(4, Wide::Word);
+        assert_eq!(
+            regs.effective_address(ea, None),
+            Address::new(0x2000, 0x0002)
+        );
+        assert_eq!(
+            regs.effective_address(ea, Some(SegmentRegister::DS)),
+            Address::new(0x1000, 0x0002)
+        );
+        let ea = EffectiveAddress::DirectAddress(0x10, Wide::Word);
+        assert_eq!(regs.effective_address(ea, None).physical(), 0x10010);
+    }
 }
@@ -1,47 +1,166 @@
-use instruction::InstructionPrefix;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
+use super::DecodeErrorKind;
 use crate::fields::Operation;
-use crate::instruction::InstructionDecoder;
+use crate::instruction::{InstructionCodec, InstructionPrefix};
 use crate::operands::*;
-use crate::*;
-use std::str::FromStr;
 
 pub enum DecoderOut {
-    Inst(Operation, Box<dyn InstructionDecoder>),
+    Inst(Operation, Box<dyn InstructionCodec>),
     Prefix(InstructionPrefix),
 }
 
+struct OpcodeEntry {
+    name: &'static str,
+    out: DecoderOut,
+    opcode: &'static [u8],
+    mask: &'static [u8],
+}
+
+const REG_FIELD_MASK: u8 = 0b00111000;
+
+fn reg_field(second: u8) -> usize {
+    ((second & REG_FIELD_MASK) >> 3) as usize
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Empty,
+    Direct(usize),
+    /// group opcodes are further dispatched on the `reg` field of the ModRM byte
+    Group([Option<usize>; 8]),
+}
+
+struct OpcodeTable {
+    entries: Vec<OpcodeEntry>,
+    slots: [Slot; 256],
+}
+
+impl OpcodeTable {
+    fn build(entries: Vec<OpcodeEntry>) -> Result<Self, String> {
+        let mut slots = [Slot::Empty; 256];
+        for (idx, entry) in entries.iter().enumerate() {
+            for first in 0..=u8::MAX {
+                if first & entry.mask[0] != entry.opcode[0] {
+                    continue;
+                }
+                let slot = &mut slots[first as usize];
+                let conflict = |other: usize| {
+                    format!(
+                        "{} and {} both match opcode {:08b}",
+                        entries[other].name, entry.name, first
+                    )
+                };
+                match (entry.opcode.len(), *slot) {
+                    (1, Slot::Empty) => *slot = Slot::Direct(idx),
+                    (2, Slot::Empty) => {
+                        *slot = Slot::Group([None; 8]);
+                        Self::fill_group(slot, idx, entry, &conflict)?;
+                    }
+                    (2, Slot::Group(_)) => Self::fill_group(slot, idx, entry, &conflict)?,
+                    (_, Slot::Direct(other)) => return Err(conflict(other)),
+                    (1, Slot::Group(group)) => {
+                        let other = group.iter().flatten().next().expect("non-empty group");
+                        return Err(conflict(*other));
+                    }
+                    _ => unreachable!("opcodes are one or two bytes long"),
+                }
+            }
+        }
+        Ok(Self { entries, slots })
+    }
+
+    fn fill_group(
+        slot: &mut Slot,
+        idx: usize,
+        entry: &OpcodeEntry,
+        conflict: &dyn Fn(usize) -> String,
+    ) -> Result<(), String> {
+        let Slot::Group(group) = slot else {
+            unreachable!()
+        };
+        let reg_mask = entry.mask[1] & REG_FIELD_MASK;
+        for (reg, target) in group.iter_mut().enumerate() {
+            if ((reg as u8) << 3) & reg_mask != entry.opcode[1] & reg_mask {
+                continue;
+            }
+            if let Some(other) = target {
+                return Err(conflict(*other));
+            }
+            *target = Some(idx);
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, first: u8, second: Option<u8>) -> Result<&DecoderOut, DecodeErrorKind> {
+        match self.slots[first as usize] {
+            Slot::Empty => Err(DecodeErrorKind::UnknownOpcode),
+            Slot::Direct(idx) => Ok(&self.entries[idx].out),
+            Slot::Group(group) => {
+                let second = second.ok_or(DecodeErrorKind::TruncatedOperand)?;
+                let entry = group[reg_field(second)]
+                    .map(|idx| &self.entries[idx])
+                    .ok_or(DecodeErrorKind::UnknownOpcode)?;
+                if second & entry.mask[1] == entry.opcode[1] {
+                    Ok(&entry.out)
+                } else {
+                    Err(DecodeErrorKind::UnknownOpcode)
+                }
+            }
+        }
+    }
+}
+
+fn opcode_table() -> &'static OpcodeTable {
+    static OPCODE_TABLE: OnceLock<OpcodeTable> = OnceLock::new();
+    OPCODE_TABLE
+        .get_or_init(|| OpcodeTable::build(opcode_entries()).expect("unambiguous opcode table"))
+}
+
+pub fn decode_instruction(
+    first: u8,
+    second: Option<u8>,
+) -> Result<&'static DecoderOut, DecodeErrorKind> {
+    opcode_table().lookup(first, second)
+}
+
+/// Operand shapes the operation can be encoded with, along with their opcode and mask
+pub fn instruction_encodings(
+    op: Operation,
+) -> impl Iterator<Item = (&'static dyn InstructionCodec, &'static [u8], &'static [u8])> {
+    opcode_table()
+        .entries
+        .iter()
+        .filter_map(move |entry| match &entry.out {
+            DecoderOut::Inst(entry_op, codec) if *entry_op == op => {
+                Some((codec.as_ref(), entry.opcode, entry.mask))
+            }
+            _ => None,
+        })
+}
+
 macro_rules! create_instruction_decoder {
     (
         $(
             ($operation:ident, $operand_type:ty, $opcode:expr, $mask:expr)
         ),*
     ) => {
-        pub fn decode_instruction(first: u8, second: Option<u8>) -> Result<DecoderOut, String> {
-            $(
-                let is_prefix = stringify!($operand_type) == "InstructionPrefix";
-                if $opcode.len() == 1 && $mask.len() == 1 {
-                    if (first & $mask[0]) == $opcode[0] {
-                        if is_prefix {
-                            return Ok(DecoderOut::Prefix(InstructionPrefix::from_str(stringify!($operation)).unwrap()));
-                        }
-                        return Ok(DecoderOut::Inst(Operation::$operation, Box::<$operand_type>::default()));
-                    }
-                } else if $opcode.len() == 2 && $mask.len() == 2 {
-                    if let Some(second_byte) = second {
-                        #[allow(unconditional_panic)]
-                        #[allow(clippy::out_of_bounds_indexing)]
-                        // https://github.com/rust-lang/rust/issues/90534
-                        if (first & $mask[0]) == $opcode[0] && (second_byte & $mask[1]) == $opcode[1] {
-                            if is_prefix {
-                                return Ok(DecoderOut::Prefix(InstructionPrefix::from_str(stringify!($operation)).unwrap()));
-                            }
-                            return Ok(DecoderOut::Inst(Operation::$operation, Box::<$operand_type>::default()));
-                        }
+        fn opcode_entries() -> Vec<OpcodeEntry> {
+            vec![
+                $(
+                    OpcodeEntry {
+                        name: stringify!($operation),
+                        out: if stringify!($operand_type) == "InstructionPrefix" {
+                            DecoderOut::Prefix(InstructionPrefix::from_str(stringify!($operation)).unwrap())
+                        } else {
+                            DecoderOut::Inst(Operation::$operation, Box::<$operand_type>::default())
+                        },
+                        opcode: &$opcode,
+                        mask: &$mask,
                     }
-                }
-            )*
-            Err(format!("Unknown opcode: {:08b} {:08b}", first, second.unwrap_or(0)))
+                ),*
+            ]
         }
     }
 }
@@ -222,7 +341,7 @@ create_instruction_decoder!(
         [0b11111110, 0b00111000]
     ),
     (AND, AccImd, [0b00100100], [0b11111110]),
-    (TEST, RegRM, [0b10000100], [0b11111100]),
+    (TEST, RegRM, [0b10000100], [0b11111110]),
     (
         TEST,
         RMImd,
@@ -292,7 +411,8 @@ create_instruction_decoder!(
     (STI, NoOps, [0b11111011], [0b11111111]),
     (HLT, NoOps, [0b11110100], [0b11111111]),
     (WAIT, NoOps, [0b10011011], [0b11111111]),
-    (Rep, InstructionPrefix, [0b11110010], [0b11111110]),
+    (RepNE, InstructionPrefix, [0b11110010], [0b11111111]),
+    (RepE, InstructionPrefix, [0b11110011], [0b11111111]),
     (Lock, InstructionPrefix, [0b11110000], [0b11111111]),
     (
         SegmentOverrideES,
@@ -319,3 +439,39 @@ create_instruction_decoder!(
         [0b11111111]
     )
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcode_table_is_unambiguous() {
+        assert_eq!(OpcodeTable::build(opcode_entries()).err(), None);
+    }
+
+    #[test]
+    fn group_opcode_lookup() {
+        let name = |first: u8, second: Option<u8>| match decode_instruction(first, second) {
+            Ok(DecoderOut::Inst(op, _)) => Ok(op.to_string()),
+            Ok(DecoderOut::Prefix(prefix)) => Ok(format!("{:?}", prefix)),
+            Err(kind) => Err(kind),
+        };
+        assert_eq!(name(0b11111111, Some(0b00110000)), Ok("push".to_string()));
+        assert_eq!(name(0b11111111, Some(0b00000000)), Ok("inc".to_string()));
+        assert_eq!(name(0b11110110, Some(0b00011000)), Ok("neg".to_string()));
+        assert_eq!(name(0b11010100, Some(0b00001010)), Ok("aam".to_string()));
+        assert_eq!(name(0b11110000, None), Ok("Lock".to_string()));
+        assert_eq!(
+            name(0b11010100, Some(0b00001011)),
+            Err(DecodeErrorKind::UnknownOpcode)
+        );
+        assert_eq!(
+            name(0b11111111, Some(0b00111000)),
+            Err(DecodeErrorKind::UnknownOpcode)
+        );
+        assert_eq!(
+            name(0b11111111, None),
+            Err(DecodeErrorKind::TruncatedOperand)
+        );
+    }
+}
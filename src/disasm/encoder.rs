@@ -0,0 +1,157 @@
+use super::{decoder::instruction_encodings, matches_opcode, EncodeError};
+use crate::{
+    fields::{sr_to_u8, Data, Operand, Operation, SegmentRegister},
+    instruction::{Inst, InstructionPrefix},
+};
+
+const LOCK: u8 = 0b11110000;
+const REPE: u8 = 0b11110011;
+const REPNE: u8 = 0b11110010;
+
+fn segment_override(sr: SegmentRegister) -> u8 {
+    0b00100110 | (sr_to_u8(sr) << 3)
+}
+
+fn encode_prefix(prefix: InstructionPrefix) -> Vec<u8> {
+    match prefix {
+        InstructionPrefix::Lock => vec![LOCK],
+        InstructionPrefix::RepE => vec![REPE],
+        InstructionPrefix::RepNE => vec![REPNE],
+        InstructionPrefix::SegmentOverride(sr) => vec![segment_override(sr)],
+        InstructionPrefix::LockSegmentOverride(sr) => vec![LOCK, segment_override(sr)],
+        InstructionPrefix::RepESegmentOverride(sr) => vec![REPE, segment_override(sr)],
+        InstructionPrefix::RepNESegmentOverride(sr) => vec![REPNE, segment_override(sr)],
+    }
+}
+
+/// Encodes the instruction into machine code, picking the shortest encoding when the operands
+/// fit more than one
+pub fn encode(inst: &Inst) -> Result<Vec<u8>, EncodeError> {
+    let mut bytes = inst.prefix.map(encode_prefix).unwrap_or_default();
+    if inst.operation == Operation::DB {
+        let (Some(Operand::Immediate(Data::U8(byte))), None) = (inst.first, inst.second) else {
+            return Err(EncodeError::InvalidOperands(inst.to_string()));
+        };
+        bytes.push(byte);
+        return Ok(bytes);
+    }
+
+    let mut encodings = instruction_encodings(inst.operation).peekable();
+    if encodings.peek().is_none() {
+        return Err(EncodeError::UnsupportedOperation(inst.operation));
+    }
+    let encoded = encodings
+        .filter_map(|(codec, opcode, mask)| {
+            codec
+                .encode(opcode, mask, inst)
+                .filter(|encoded| matches_opcode(encoded, opcode, mask))
+        })
+        .min_by_key(Vec::len)
+        .ok_or_else(|| EncodeError::InvalidOperands(inst.to_string()))?;
+    bytes.extend(encoded);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        disasm::{decode_next, operand_is_wide, try_decode_8086},
+        fields::{EffectiveAddress, Register, Wide},
+    };
+
+    fn reencode(bytes: &[u8]) -> Vec<u8> {
+        let inst = try_decode_8086(bytes).unwrap().remove(0);
+        encode(&inst).unwrap()
+    }
+
+    #[test]
+    fn encode_picks_shortest_form() {
+        // mov ax, [5] fits the accumulator form
+        let mov = Inst::with_operands(
+            Operation::Mov,
+            Register::AX.into(),
+            EffectiveAddress::DirectAddress(5, Wide::Word).into(),
+        );
+        assert_eq!(encode(&mov), Ok(vec![0b10100001, 0b00000101, 0b00000000]));
+        // add word [bx + 300], 5 sign-extends the immediate and keeps the 16-bit displacement
+        assert_eq!(
+            reencode(&[0b10000001, 0b10000111, 0x2c, 0x01, 0x05, 0x00]),
+            [0b10000011, 0b10000111, 0x2c, 0x01, 0x05]
+        );
+        // mov cx, [bp] needs a zero displacement
+        assert_eq!(
+            reencode(&[0b10001011, 0b10001110, 0x00, 0x00]),
+            [0b10001011, 0b01001110, 0x00]
+        );
+    }
+
+    #[test]
+    fn encode_prefixes() {
+        assert_eq!(
+            reencode(&[0b11110000, 0b00101110, 0b10000110, 0b00000111]),
+            [0b11110000, 0b00101110, 0b10000110, 0b00000111]
+        );
+        assert_eq!(
+            reencode(&[0b11110010, 0b10100110]),
+            [0b11110010, 0b10100110]
+        );
+        assert_eq!(
+            reencode(&[0b11110011, 0b10100100]),
+            [0b11110011, 0b10100100]
+        );
+        assert_eq!(encode(&Inst::data_byte(0x60)), Ok(vec![0x60]));
+    }
+
+    #[test]
+    fn encode_errors() {
+        let push = Inst::with_operand(Operation::Push, Register::AL.into());
+        assert_eq!(
+            encode(&push),
+            Err(EncodeError::InvalidOperands("push al".to_string()))
+        );
+    }
+
+    /// Every decodable instruction (for all first two bytes, and a couple of operand tails)
+    /// decodes back to itself after encoding, except the 0x82 alias that nothing assembles to
+    #[test]
+    fn decode_encode_round_trip() {
+        const TAILS: [[u8; 4]; 2] = [[0x12, 0x34, 0x56, 0x78], [0xfe, 0xff, 0x80, 0x00]];
+        for first in 0..=u8::MAX {
+            for second in 0..=u8::MAX {
+                for tail in TAILS {
+                    let mut bytes = vec![first, second];
+                    bytes.extend(tail);
+                    let Ok(inst) = decode_next(&bytes, 0) else {
+                        continue;
+                    };
+                    // a byte destination with 16-bit data only comes from 0x82
+                    if let (Some(first), Some(Operand::Immediate(Data::U16(_)))) =
+                        (inst.first, inst.second)
+                    {
+                        if operand_is_wide(first) == Some(false) {
+                            assert!(encode(&inst).is_err(), "{:02x?} encoded", bytes);
+                            continue;
+                        }
+                    }
+                    let encoded = encode(&inst)
+                        .unwrap_or_else(|err| panic!("{:02x?} ({}): {}", bytes, inst, err));
+                    let decoded = decode_next(&encoded, 0).unwrap();
+                    assert_eq!(
+                        (
+                            decoded.operation,
+                            decoded.first,
+                            decoded.second,
+                            decoded.prefix
+                        ),
+                        (inst.operation, inst.first, inst.second, inst.prefix),
+                        "{:02x?} re-encoded as {:02x?}",
+                        bytes,
+                        encoded
+                    );
+                    assert_eq!(decoded.size(), Some(encoded.len()));
+                }
+            }
+        }
+    }
+}
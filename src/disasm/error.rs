@@ -0,0 +1,79 @@
+use std::{fmt, io};
+
+use crate::fields::Operation;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeErrorKind {
+    /// first byte (and ModRM byte, for group opcodes) doesn't match any known instruction
+    UnknownOpcode,
+    /// input ended before all displacement / data bytes of the instruction were read
+    TruncatedOperand,
+    /// input ended right after one or more instruction prefixes
+    DanglingPrefix,
+    /// instruction, with its prefixes, runs past the longest the stream decoder buffers
+    TooLong,
+    /// the underlying reader failed
+    Io(io::ErrorKind),
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode => write!(f, "unknown opcode"),
+            Self::TruncatedOperand => write!(f, "truncated operand"),
+            Self::DanglingPrefix => write!(f, "dangling prefix"),
+            Self::TooLong => write!(f, "instruction too long"),
+            Self::Io(kind) => write!(f, "read error ({})", kind),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    /// byte offset of the instruction (including its prefixes) in the input
+    pub offset: usize,
+    /// bytes of the instruction that were consumed before the error was detected
+    pub bytes: Vec<u8>,
+}
+
+impl DecodeError {
+    pub fn new(kind: DecodeErrorKind, offset: usize, bytes: &[u8]) -> Self {
+        Self {
+            kind,
+            offset,
+            bytes: bytes.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {:#06x}:", self.kind, self.offset)?;
+        for byte in &self.bytes {
+            write!(f, " {:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EncodeError {
+    /// the operation has no machine-code form
+    UnsupportedOperation(Operation),
+    /// none of the operation's encodings accept the instruction's operands
+    InvalidOperands(String),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOperation(op) => write!(f, "unsupported operation: {}", op),
+            Self::InvalidOperands(inst) => write!(f, "invalid operands: {}", inst),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
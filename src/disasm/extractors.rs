@@ -1,14 +1,71 @@
 use crate::fields::{
-    register_from_u8, sr_from_u8, CsIp, Data, EffectiveAddress, Inc, Register, SegmentRegister,
-    Wide, RM,
+    register_from_u8, register_to_u8, sr_from_u8, sr_to_u8, CsIp, Data, EffectiveAddress, Inc,
+    Operand, Register, SegmentRegister, Wide, RM,
 };
 
+use super::DecodeErrorKind;
+
+pub fn next_byte<'a, I>(byte_stream: &mut I) -> Result<u8, DecodeErrorKind>
+where
+    I: Iterator<Item = &'a u8>,
+{
+    byte_stream
+        .next()
+        .copied()
+        .ok_or(DecodeErrorKind::TruncatedOperand)
+}
+
+pub fn next_u16<'a, I>(byte_stream: &mut I) -> Result<u16, DecodeErrorKind>
+where
+    I: Iterator<Item = &'a u8>,
+{
+    let low = next_byte(byte_stream)?;
+    let high = next_byte(byte_stream)?;
+    Ok(u16::from_le_bytes([low, high]))
+}
+
+/// Checks the fixed bits of an encoded instruction against the opcode pattern it was built from
+pub fn matches_opcode(bytes: &[u8], opcode: &[u8], mask: &[u8]) -> bool {
+    bytes.len() >= opcode.len()
+        && opcode
+            .iter()
+            .zip(mask)
+            .zip(bytes)
+            .all(|((&op, &m), &b)| b & m == op)
+}
+
+/// Whether a register or sized memory operand is 16 bits wide
+pub fn operand_is_wide(operand: Operand) -> Option<bool> {
+    match operand {
+        Operand::Register(reg) => Some(reg.is_wide()),
+        Operand::EffectiveAddress(ea) => match ea.wide() {
+            Wide::Byte => Some(false),
+            Wide::Word => Some(true),
+            Wide::None => None,
+        },
+        _ => None,
+    }
+}
+
+/// Sign-extended 8-bit form of a 16-bit value, if it has one
+fn as_i8(value: u16) -> Option<u8> {
+    i8::try_from(value as i16).ok().map(|x| x as u8)
+}
+
 pub trait WithSignField {
     const SIGN_MASK_MATCH: u8 = 0b00000010;
 
     fn sign_extend(first_byte: u8) -> bool {
         (first_byte & Self::SIGN_MASK_MATCH) == Self::SIGN_MASK_MATCH
     }
+
+    fn emit_sign(sign_extend: bool) -> u8 {
+        if sign_extend {
+            Self::SIGN_MASK_MATCH
+        } else {
+            0
+        }
+    }
 }
 
 pub trait WithWideField {
@@ -25,6 +82,23 @@ pub trait WithWideField {
             Self::is_wide(first_byte).into()
         }
     }
+
+    fn emit_wide(wide: bool) -> u8 {
+        if wide {
+            Self::WIDE_MASK_MATCH
+        } else {
+            0
+        }
+    }
+
+    /// Width recorded in effective addresses decoded with the given wide bit
+    fn expected_ea_wide(wide: bool) -> Wide {
+        if Self::WIDE_MASK_MATCH == 0 {
+            Wide::None
+        } else {
+            wide.into()
+        }
+    }
 }
 
 pub trait WithDestField {
@@ -33,6 +107,20 @@ pub trait WithDestField {
     fn is_dest_in_reg_field(first_byte: u8) -> bool {
         (first_byte & Self::DEST_MASK_MATCH) == Self::DEST_MASK_MATCH
     }
+
+    fn emit_dest(is_dest_in_reg_field: bool) -> u8 {
+        if is_dest_in_reg_field {
+            Self::DEST_MASK_MATCH
+        } else {
+            0
+        }
+    }
+
+    /// Whether the opcode pattern leaves the direction free or pins it to the given one
+    fn allows_dest(opcode: u8, mask: u8, is_dest_in_reg_field: bool) -> bool {
+        mask & Self::DEST_MASK_MATCH == 0
+            || opcode & Self::DEST_MASK_MATCH == Self::emit_dest(is_dest_in_reg_field)
+    }
 }
 
 pub trait WithRMField: WithWideField {
@@ -49,25 +137,30 @@ pub trait WithRMField: WithWideField {
         (second_byte >> Self::RM_RIGHT_SHIFT_BY) & RM_MASK
     }
 
-    fn extract_disp<'a, I>(modf: u8, rm: u8, byte_stream: &mut I) -> Option<u16>
+    fn extract_disp<'a, I>(
+        modf: u8,
+        rm: u8,
+        byte_stream: &mut I,
+    ) -> Result<Option<u16>, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
         if modf == 0b01 {
-            let data = byte_stream.next().expect("extract disp-low").to_owned();
+            let data = next_byte(byte_stream)?;
             let sign_extended = ((data as i8) as i16) as u16;
-            Some(sign_extended)
+            Ok(Some(sign_extended))
         } else if modf == 0b10 || (modf == 0b00 && rm == 0b110) {
-            let data_low = byte_stream.next().expect("extract disp-low").to_owned();
-            let data_high = byte_stream.next().expect("extract disp-high").to_owned();
-            let data: u16 = ((data_high as u16) << 8) | (data_low as u16);
-            Some(data)
+            Ok(Some(next_u16(byte_stream)?))
         } else {
-            None
+            Ok(None)
         }
     }
 
-    fn extract_rm<'a, I>(first_byte: u8, second_byte: u8, byte_stream: &mut I) -> RM
+    fn extract_rm<'a, I>(
+        first_byte: u8,
+        second_byte: u8,
+        byte_stream: &mut I,
+    ) -> Result<RM, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
@@ -75,8 +168,8 @@ pub trait WithRMField: WithWideField {
         let ws = Self::get_wide_size(first_byte);
         let modf = Self::extract_mod(second_byte);
         let rm = Self::extract_rm_bin(second_byte);
-        let disp = Self::extract_disp(modf, rm, byte_stream);
-        if modf == 0b11 {
+        let disp = Self::extract_disp(modf, rm, byte_stream)?;
+        Ok(if modf == 0b11 {
             RM::Reg(register_from_u8(rm, wide))
         } else if modf == 0b00 && rm == 0b110 {
             RM::Mem(EffectiveAddress::DirectAddress(
@@ -95,8 +188,51 @@ pub trait WithRMField: WithWideField {
                 0b111 => RM::Mem(EffectiveAddress::BX(disp, ws)),
                 _ => unreachable!(),
             }
+        })
+    }
+
+    fn emit_disp(disp: Option<u16>) -> (u8, Vec<u8>) {
+        match disp {
+            None => (0b00, Vec::new()),
+            Some(disp) => match as_i8(disp) {
+                Some(disp) => (0b01, vec![disp]),
+                None => (0b10, disp.to_le_bytes().to_vec()),
+            },
         }
     }
+
+    /// Encodes a register or memory operand of the given width into the mod and r/m fields of a
+    /// ModRM byte, followed by any displacement bytes
+    fn emit_rm(rm: Operand, wide: bool) -> Option<(u8, Vec<u8>)> {
+        let ea = match rm {
+            Operand::Register(reg) if reg.is_wide() == wide => {
+                return Some((
+                    (0b11 << Self::MOD_RIGHT_SHIFT_BY)
+                        | (register_to_u8(reg) << Self::RM_RIGHT_SHIFT_BY),
+                    Vec::new(),
+                ));
+            }
+            Operand::EffectiveAddress(ea) if ea.wide() == Self::expected_ea_wide(wide) => ea,
+            _ => return None,
+        };
+        let ((modf, disp), rm) = match ea {
+            EffectiveAddress::DirectAddress(addr, _) => {
+                ((0b00, addr.to_le_bytes().to_vec()), 0b110)
+            }
+            EffectiveAddress::BX_SI(disp, _) => (Self::emit_disp(disp), 0b000),
+            EffectiveAddress::BX_DI(disp, _) => (Self::emit_disp(disp), 0b001),
+            EffectiveAddress::BP_SI(disp, _) => (Self::emit_disp(disp), 0b010),
+            EffectiveAddress::BP_DI(disp, _) => (Self::emit_disp(disp), 0b011),
+            EffectiveAddress::SI(disp, _) => (Self::emit_disp(disp), 0b100),
+            EffectiveAddress::DI(disp, _) => (Self::emit_disp(disp), 0b101),
+            EffectiveAddress::BP(disp, _) => (Self::emit_disp(Some(disp)), 0b110),
+            EffectiveAddress::BX(disp, _) => (Self::emit_disp(disp), 0b111),
+        };
+        Some((
+            (modf << Self::MOD_RIGHT_SHIFT_BY) | (rm << Self::RM_RIGHT_SHIFT_BY),
+            disp,
+        ))
+    }
 }
 
 pub trait WithRegField: WithWideField {
@@ -109,28 +245,36 @@ pub trait WithRegField: WithWideField {
         let reg = reg_byte & REG_MASK;
         register_from_u8(reg, wide)
     }
+
+    fn emit_reg(reg: Register, wide: bool) -> Option<u8> {
+        (reg.is_wide() == wide).then(|| register_to_u8(reg) << Self::RIGHT_SHIFT_BY)
+    }
 }
 
 pub trait WithData: WithWideField {
-    fn extract_data<'a, I>(first_byte: u8, byte_stream: &mut I) -> Data
+    fn extract_data<'a, I>(first_byte: u8, byte_stream: &mut I) -> Result<Data, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
         let wide = Self::is_wide(first_byte);
         if wide {
-            let data_low = byte_stream.next().expect("extract data-low").to_owned();
-            let data_high = byte_stream.next().expect("extract data-high").to_owned();
-            let data: u16 = ((data_high as u16) << 8) | (data_low as u16);
-            Data::U16(data)
+            Ok(Data::U16(next_u16(byte_stream)?))
         } else {
-            let data = byte_stream.next().expect("extract data-8").to_owned();
-            Data::U8(data)
+            Ok(Data::U8(next_byte(byte_stream)?))
+        }
+    }
+
+    fn emit_data(data: Data, wide: bool) -> Option<Vec<u8>> {
+        match data {
+            Data::U16(x) if wide => Some(x.to_le_bytes().to_vec()),
+            Data::U8(x) if !wide => Some(vec![x]),
+            _ => None,
         }
     }
 }
 
 pub trait WithDataS: WithWideField + WithSignField {
-    fn extract_data<'a, I>(first_byte: u8, byte_stream: &mut I) -> Data
+    fn extract_data<'a, I>(first_byte: u8, byte_stream: &mut I) -> Result<Data, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
@@ -138,29 +282,45 @@ pub trait WithDataS: WithWideField + WithSignField {
         let sign = Self::sign_extend(first_byte);
         if !sign {
             if wide {
-                let data_low = byte_stream.next().expect("extract data-low").to_owned();
-                let data_high = byte_stream.next().expect("extract data-high").to_owned();
-                let data: u16 = ((data_high as u16) << 8) | (data_low as u16);
-                Data::U16(data)
+                Ok(Data::U16(next_u16(byte_stream)?))
             } else {
-                let data = byte_stream.next().expect("extract data-8").to_owned();
-                Data::U8(data)
+                Ok(Data::U8(next_byte(byte_stream)?))
             }
         } else {
-            let data = byte_stream.next().expect("extract data").to_owned();
+            let data = next_byte(byte_stream)?;
             let sign_extended = ((data as i8) as i16) as u16;
-            Data::U16(sign_extended)
+            Ok(Data::U16(sign_extended))
+        }
+    }
+
+    /// Returns the sign bit to set along with the data bytes. Byte-sized destinations with 16-bit
+    /// data only come from the 0x82 alias, which NASM never emits, so they aren't encoded.
+    fn emit_data(data: Data, wide: bool) -> Option<(u8, Vec<u8>)> {
+        match data {
+            Data::U8(x) if !wide => Some((Self::emit_sign(false), vec![x])),
+            Data::U16(x) if wide => match as_i8(x) {
+                Some(x) => Some((Self::emit_sign(true), vec![x])),
+                None => Some((Self::emit_sign(false), x.to_le_bytes().to_vec())),
+            },
+            _ => None,
         }
     }
 }
 
 pub trait WithInc8 {
-    fn extract_inc8<'a, I>(_first_byte: u8, byte_stream: &mut I) -> Inc
+    fn extract_inc8<'a, I>(_first_byte: u8, byte_stream: &mut I) -> Result<Inc, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let data = byte_stream.next().expect("extract inc-8").to_owned();
-        Inc::I8(data as i8)
+        let data = next_byte(byte_stream)?;
+        Ok(Inc::I8(data as i8))
+    }
+
+    fn emit_inc8(inc: Inc) -> Option<Vec<u8>> {
+        match inc {
+            Inc::I8(x) => Some(vec![x as u8]),
+            Inc::I16(_) => None,
+        }
     }
 }
 
@@ -170,6 +330,10 @@ pub trait WithSR {
     fn extract_sr(sr_byte: u8) -> SegmentRegister {
         sr_from_u8((sr_byte >> Self::SR_RIGHT_SHIFT_BY) & 0b00000011)
     }
+
+    fn emit_sr(sr: SegmentRegister) -> u8 {
+        sr_to_u8(sr) << Self::SR_RIGHT_SHIFT_BY
+    }
 }
 
 pub trait WithVField {
@@ -178,58 +342,82 @@ pub trait WithVField {
     fn is_v_set(v_byte: u8) -> bool {
         (v_byte & Self::V_MASK_MATCH) == Self::V_MASK_MATCH
     }
+
+    fn emit_v(is_v_set: bool) -> u8 {
+        if is_v_set {
+            Self::V_MASK_MATCH
+        } else {
+            0
+        }
+    }
 }
 
 pub trait WithInc16 {
-    fn extract_inc16<'a, I>(byte_stream: &mut I) -> Inc
+    fn extract_inc16<'a, I>(byte_stream: &mut I) -> Result<Inc, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let inc_low = byte_stream.next().expect("extract inc-low").to_owned();
-        let inc_high = byte_stream.next().expect("extract inc-high").to_owned();
-        let inc: i16 = ((inc_high as i16) << 8) | (inc_low as i16);
-        Inc::I16(inc)
+        let inc = next_u16(byte_stream)?;
+        Ok(Inc::I16(inc as i16))
+    }
+
+    fn emit_inc16(inc: Inc) -> Option<Vec<u8>> {
+        match inc {
+            Inc::I16(x) => Some(x.to_le_bytes().to_vec()),
+            Inc::I8(_) => None,
+        }
     }
 }
 
 pub trait WithData16 {
-    fn extract_data16<'a, I>(byte_stream: &mut I) -> Data
+    fn extract_data16<'a, I>(byte_stream: &mut I) -> Result<Data, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let data_low = byte_stream.next().expect("extract data-low").to_owned();
-        let data_high = byte_stream.next().expect("extract data-high").to_owned();
-        let data: u16 = ((data_high as u16) << 8) | (data_low as u16);
-        Data::U16(data)
+        Ok(Data::U16(next_u16(byte_stream)?))
+    }
+
+    fn emit_data16(data: Data) -> Option<Vec<u8>> {
+        match data {
+            Data::U16(x) => Some(x.to_le_bytes().to_vec()),
+            Data::U8(_) => None,
+        }
     }
 }
 
 pub trait WithData8 {
-    fn extract_data8<'a, I>(byte_stream: &mut I) -> Data
+    fn extract_data8<'a, I>(byte_stream: &mut I) -> Result<Data, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let data = byte_stream.next().expect("extract data-8").to_owned();
-        Data::U8(data)
+        Ok(Data::U8(next_byte(byte_stream)?))
+    }
+
+    fn emit_data8(data: Data) -> Option<Vec<u8>> {
+        match data {
+            Data::U8(x) => Some(vec![x]),
+            Data::U16(_) => None,
+        }
     }
 }
 
 pub trait WithCsIp {
-    fn extract_cs_ip<'a, I>(byte_stream: &mut I) -> CsIp
+    fn extract_cs_ip<'a, I>(byte_stream: &mut I) -> Result<CsIp, DecodeErrorKind>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let ip_low = byte_stream.next().expect("extract ip-low").to_owned();
-        let ip_high = byte_stream.next().expect("extract ip-high").to_owned();
-        let ip: u16 = ((ip_high as u16) << 8) | (ip_low as u16);
+        let ip = next_u16(byte_stream)?;
+        let cs = next_u16(byte_stream)?;
 
-        let cs_low = byte_stream.next().expect("extract cs-low").to_owned();
-        let cs_high = byte_stream.next().expect("extract cs-high").to_owned();
-        let cs: u16 = ((cs_high as u16) << 8) | (cs_low as u16);
-
-        CsIp {
+        Ok(CsIp {
             code_segment: cs,
             instruction_pointer: ip,
-        }
+        })
+    }
+
+    fn emit_cs_ip(cs_ip: CsIp) -> Vec<u8> {
+        let mut bytes = cs_ip.instruction_pointer.to_le_bytes().to_vec();
+        bytes.extend(cs_ip.code_segment.to_le_bytes());
+        bytes
     }
 }
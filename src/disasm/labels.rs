@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{
+    fields::{Inc, Operand, Operation},
+    instruction::Inst,
+};
+
+/// Pairs every instruction with its byte offset from the start of the decoded stream.
+/// Instructions without a recorded size don't advance the offset.
+pub fn with_offsets(instructions: &[Inst]) -> impl Iterator<Item = (usize, &Inst)> {
+    instructions.iter().scan(0, |offset, inst| {
+        let current = *offset;
+        *offset += inst.size().unwrap_or(0);
+        Some((current, inst))
+    })
+}
+
+/// Absolute target of a relative jump, loop or call.
+pub fn branch_target(offset: usize, inst: &Inst) -> Option<usize> {
+    let size = inst.size()?;
+    let Some(Operand::Increment(inc)) = inst.first else {
+        return None;
+    };
+    let target = (offset + size) as isize + i16::from(inc) as isize;
+    usize::try_from(target).ok()
+}
+
+/// Branch targets that land on an instruction boundary (or right after the last instruction),
+/// so that they can be referenced by name rather than by a relative increment.
+#[derive(Debug, Default)]
+pub struct Labels(BTreeSet<usize>);
+
+impl Labels {
+    pub fn new(instructions: &[Inst]) -> Self {
+        if instructions.iter().any(|inst| inst.size().is_none()) {
+            return Self::default();
+        }
+        let mut boundaries: BTreeSet<usize> = with_offsets(instructions)
+            .map(|(offset, _)| offset)
+            .collect();
+        boundaries.insert(instructions.iter().filter_map(Inst::size).sum());
+
+        let targets = with_offsets(instructions)
+            .filter_map(|(offset, inst)| branch_target(offset, inst))
+            .filter(|target| boundaries.contains(target))
+            .collect();
+        Self(targets)
+    }
+
+    pub fn at(&self, offset: usize) -> Option<Label> {
+        self.0.contains(&offset).then_some(Label(offset))
+    }
+
+    /// Label of the instruction's branch target, if there is one
+    pub fn target(&self, offset: usize, inst: &Inst) -> Option<Label> {
+        branch_target(offset, inst).and_then(|target| self.at(target))
+    }
+
+    /// Renders the instruction, referring to its branch target by label when possible
+    pub fn render(&self, offset: usize, inst: &Inst) -> String {
+        match self.target(offset, inst) {
+            Some(label) if inst.prefix.is_none() => {
+                // Pin the jump size, otherwise NASM is free to pick a different encoding
+                let qualifier = match inst.first {
+                    Some(Operand::Increment(Inc::I8(_))) if inst.operation == Operation::Jmp => {
+                        "short "
+                    }
+                    Some(Operand::Increment(Inc::I16(_))) if inst.operation == Operation::Jmp => {
+                        "near "
+                    }
+                    _ => "",
+                };
+                format!("{} {}{}", inst.operation, qualifier, label)
+            }
+            _ => inst.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Label(pub usize);
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "label_{:04x}", self.0)
+    }
+}
@@ -1,9 +1,17 @@
 mod decoder;
+mod encoder;
+mod error;
 mod extractors;
+mod labels;
 mod program;
+mod stream;
 
+pub use encoder::*;
+pub use error::*;
 pub use extractors::*;
+pub use labels::*;
 pub use program::*;
+pub use stream::*;
 
 use std::io::{self, Write};
 
@@ -13,62 +21,127 @@ use crate::{
 };
 use decoder::{decode_instruction, DecoderOut};
 
-fn join_prefix(prev: InstructionPrefix, curr: InstructionPrefix) -> InstructionPrefix {
-    match (prev, curr) {
-        (InstructionPrefix::Lock, InstructionPrefix::SegmentOverride(seg))
-        | (InstructionPrefix::SegmentOverride(seg), InstructionPrefix::Lock) => {
-            InstructionPrefix::LockSegmentOverride(seg)
-        }
-        _ => curr,
-    }
-}
-
-pub fn decode_8086(byte_stream_raw: &[u8]) -> Vec<Inst> {
-    let mut byte_stream = ByteStream::new(byte_stream_raw.iter());
-    let mut instructions: Vec<Inst> = Vec::new();
-    let mut first_instruction_byte = byte_stream.next_with_index();
+/// Decodes a single instruction, along with any prefixes preceding it, starting at `offset`.
+/// The returned instruction has its size set.
+pub(crate) fn decode_next(byte_stream_raw: &[u8], offset: usize) -> Result<Inst, DecodeError> {
+    let raw = &byte_stream_raw[offset..];
+    let mut byte_stream = ByteStream::new(raw.iter());
     let mut inst_prefix: Option<InstructionPrefix> = None;
-    let mut start_idx = 0;
-    while let Some((end_idx, &first_byte)) = first_instruction_byte {
-        if end_idx != start_idx {
-            instructions
-                .last_mut()
-                .expect("atleast one instruction is decoded")
-                .set_size(end_idx - start_idx);
-            start_idx = end_idx;
-        }
+    while let Some(&first_byte) = byte_stream.next() {
         let second_byte = byte_stream.peek().map(|&v| *v);
-        match decode_instruction(first_byte, second_byte).unwrap() {
+        let decoded = decode_instruction(first_byte, second_byte)
+            .map_err(|kind| DecodeError::new(kind, offset, &raw[..byte_stream.vended_count()]))?;
+        match decoded {
             DecoderOut::Inst(op, decoder) => {
-                let mut inst = decoder.decode(first_byte, &mut byte_stream, op);
-                if let Some(prefix) = inst_prefix.take() {
+                let mut inst =
+                    decoder
+                        .decode(first_byte, &mut byte_stream, *op)
+                        .map_err(|kind| {
+                            DecodeError::new(kind, offset, &raw[..byte_stream.vended_count()])
+                        })?;
+                if let Some(prefix) = inst_prefix {
                     inst.add_instruction_prefix(prefix);
                 }
-                instructions.push(inst);
+                inst.set_bytes(&raw[..byte_stream.vended_count()]);
+                return Ok(inst);
             }
             DecoderOut::Prefix(prefix) => {
-                if inst_prefix.is_some() {
-                    inst_prefix.replace(join_prefix(prefix, inst_prefix.unwrap()));
-                } else {
-                    inst_prefix.replace(prefix);
-                }
+                // a pair that can't be carried together keeps the later prefix
+                inst_prefix = Some(
+                    inst_prefix
+                        .and_then(|prev| prev.join(*prefix))
+                        .unwrap_or(*prefix),
+                );
             }
         }
-        first_instruction_byte = byte_stream.next_with_index();
     }
-    if start_idx != 0 || !byte_stream_raw.is_empty() {
-        instructions
-            .last_mut()
-            .expect("atleast one instruction is decoded")
-            .set_size(byte_stream.vended_count() - start_idx);
+    Err(DecodeError::new(
+        DecodeErrorKind::DanglingPrefix,
+        offset,
+        raw,
+    ))
+}
+
+/// Decodes the whole byte stream, stopping at the first instruction that can't be decoded.
+pub fn try_decode_8086(byte_stream_raw: &[u8]) -> Result<Vec<Inst>, DecodeError> {
+    let mut instructions: Vec<Inst> = Vec::new();
+    let mut offset = 0;
+    while offset < byte_stream_raw.len() {
+        let inst = decode_next(byte_stream_raw, offset)?;
+        offset += inst.size().expect("decoded instruction has size");
+        instructions.push(inst);
+    }
+    Ok(instructions)
+}
+
+pub fn decode_8086(byte_stream_raw: &[u8]) -> Vec<Inst> {
+    try_decode_8086(byte_stream_raw).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Decodes the whole byte stream, emitting a `db` pseudo-instruction for every byte that doesn't
+/// start a valid instruction and resynchronising at the byte after it. Instructions that wouldn't
+/// assemble back to the same bytes, such as non-canonical encodings or prefixes the listing
+/// can't show, are emitted as `db` bytes too.
+pub fn decode_8086_lenient(byte_stream_raw: &[u8]) -> Vec<Inst> {
+    let mut instructions: Vec<Inst> = Vec::new();
+    let mut offset = 0;
+    while offset < byte_stream_raw.len() {
+        match decode_next(byte_stream_raw, offset) {
+            Ok(inst) if encode(&inst).is_ok_and(|bytes| bytes == inst.bytes()) => {
+                offset += inst.size().expect("decoded instruction has size");
+                instructions.push(inst);
+            }
+            Ok(inst) => {
+                instructions.extend(inst.bytes().iter().map(|&byte| Inst::data_byte(byte)));
+                offset += inst.bytes().len();
+            }
+            Err(_) => {
+                instructions.push(Inst::data_byte(byte_stream_raw[offset]));
+                offset += 1;
+            }
+        }
     }
     instructions
 }
 
-pub fn write_8086(instructions: &Vec<Inst>, f: &mut impl Write) -> Result<(), io::Error> {
+pub fn write_8086(instructions: &[Inst], f: &mut impl Write) -> Result<(), io::Error> {
     writeln!(f, "bits 16;")?;
-    for instruction in instructions {
-        writeln!(f, "{}", instruction)?;
+    let labels = Labels::new(instructions);
+    let mut end = 0;
+    for (offset, instruction) in with_offsets(instructions) {
+        if let Some(label) = labels.at(offset) {
+            writeln!(f, "{}:", label)?;
+        }
+        writeln!(f, "{}", labels.render(offset, instruction))?;
+        end = offset + instruction.size().unwrap_or(0);
+    }
+    if let Some(label) = labels.at(end) {
+        writeln!(f, "{}:", label)?;
+    }
+    Ok(())
+}
+
+/// Writes an objdump-style listing: offset, raw bytes and the disassembled instruction
+pub fn write_8086_listing(instructions: &[Inst], f: &mut impl Write) -> Result<(), io::Error> {
+    const BYTES_COLUMN_WIDTH: usize = MAX_INSTRUCTION_LEN * 3;
+    let labels = Labels::new(instructions);
+    for (offset, instruction) in with_offsets(instructions) {
+        if let Some(label) = labels.at(offset) {
+            writeln!(f, "{:04x}  {}:", offset, label)?;
+        }
+        let hex: Vec<String> = instruction
+            .bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        writeln!(
+            f,
+            "{:04x}  {:<width$}  {}",
+            offset,
+            hex.join(" "),
+            labels.render(offset, instruction),
+            width = BYTES_COLUMN_WIDTH
+        )?;
     }
     Ok(())
 }
@@ -76,6 +149,7 @@ pub fn write_8086(instructions: &Vec<Inst>, f: &mut impl Write) -> Result<(), io
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::fields::SegmentRegister;
 
     #[test]
     fn decode() {
@@ -87,4 +161,172 @@ pub mod tests {
         assert_eq!(instructions[0].to_string(), "mov word [256], ax");
         assert_eq!(instructions[1].to_string(), "mov word [si + 4], 256");
     }
+
+    #[test]
+    fn decode_repeat_prefixes() {
+        // rep movsb; repe cmpsw; repne scasb
+        let bytes: [u8; 6] = [0xf3, 0xa4, 0xf3, 0xa7, 0xf2, 0xae];
+        let listing: Vec<String> = decode_8086(&bytes[..])
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(listing, ["rep movsb", "repe cmpsw", "repne scasb"]);
+    }
+
+    #[test]
+    fn decode_repeat_with_segment_override() {
+        // rep es movsb, with the prefixes in either order; cs lodsw
+        let bytes: [u8; 8] = [0xf3, 0x26, 0xa4, 0x26, 0xf3, 0xa4, 0x2e, 0xad];
+        let instructions = decode_8086(&bytes[..]);
+        let listing: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(listing, ["rep es movsb", "rep es movsb", "cs lodsw"]);
+        let prefix = Some(InstructionPrefix::RepESegmentOverride(SegmentRegister::ES));
+        assert_eq!(
+            [instructions[0].prefix, instructions[1].prefix],
+            [prefix, prefix]
+        );
+    }
+
+    #[test]
+    fn write_labels() {
+        // mov cx, 3; dec cx; jne -3; jne -7; jmp 0
+        let bytes: [u8; 10] = [
+            0b10111001, 0b00000011, 0b00000000, 0b01001001, 0b01110101, 0b11111101, 0b01110101,
+            0b11111001, 0b11101011, 0b00000000,
+        ];
+        let mut out = Vec::new();
+        write_8086(&decode_8086(&bytes[..]), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "bits 16;\n\
+             mov cx, 3\n\
+             label_0003:\n\
+             dec cx\n\
+             jne label_0003\n\
+             jne $+2+-7\n\
+             jmp short label_000a\n\
+             label_000a:\n"
+        );
+    }
+
+    #[test]
+    fn write_listing() {
+        // mov cx, 3; dec cx; jne -3; lock xchg [100], al; lock add word es:[bx + 300], 5
+        let bytes: [u8; 19] = [
+            0b10111001, 0b00000011, 0b00000000, 0b01001001, 0b01110101, 0b11111101, 0b11110000,
+            0b10000110, 0b00000110, 0b01100100, 0b00000000, 0xf0, 0x26, 0x81, 0x87, 0x2c, 0x01,
+            0x05, 0x00,
+        ];
+        let mut out = Vec::new();
+        write_8086_listing(&decode_8086(&bytes[..]), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0000  b9 03 00                     mov cx, 3\n\
+             0003  label_0003:\n\
+             0003  49                           dec cx\n\
+             0004  75 fd                        jne label_0003\n\
+             0006  f0 86 06 64 00               lock xchg byte [100], al\n\
+             000b  f0 26 81 87 2c 01 05 00      lock add word es:[bx + 300], 5\n"
+        );
+    }
+
+    #[test]
+    fn decode_lenient() {
+        let bytes: [u8; 7] = [
+            0b01100000, 0b10001001, 0b11000011, 0b11110000, 0b01100000, 0b10111000, 0b00000001,
+        ];
+        let instructions = decode_8086_lenient(&bytes[..]);
+        let listing: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            listing,
+            [
+                "db 0x60",
+                "mov bx, ax",
+                "db 0xf0",
+                "db 0x60",
+                "db 0xb8",
+                "db 0x01"
+            ]
+        );
+        assert_eq!(
+            instructions.iter().filter_map(|i| i.size()).sum::<usize>(),
+            bytes.len()
+        );
+    }
+
+    #[test]
+    fn decode_lenient_keeps_non_canonical_encodings() {
+        // mov bx, ax with the operands swapped in the ModRM byte; add al, 1 through the 0x82
+        // alias; rep es movsb with the override first; a canonical mov bx, ax
+        let bytes: [u8; 10] = [0x8b, 0xd8, 0x82, 0xc0, 0x01, 0x26, 0xf3, 0xa4, 0x89, 0xc3];
+        let instructions = decode_8086_lenient(&bytes[..]);
+        let listing: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            listing,
+            [
+                "db 0x8b",
+                "db 0xd8",
+                "db 0x82",
+                "db 0xc0",
+                "db 0x01",
+                "db 0x26",
+                "db 0xf3",
+                "db 0xa4",
+                "mov bx, ax"
+            ]
+        );
+        let reassembled: Vec<u8> = instructions
+            .iter()
+            .flat_map(|i| encode(i).unwrap())
+            .collect();
+        assert_eq!(reassembled, bytes);
+    }
+
+    #[test]
+    fn decode_error_unknown_opcode() {
+        let bytes: [u8; 3] = [0b10001001, 0b11000011, 0b01100000];
+        assert_eq!(
+            try_decode_8086(&bytes[..]),
+            Err(DecodeError::new(
+                DecodeErrorKind::UnknownOpcode,
+                2,
+                &[0b01100000]
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_error_truncated_operand() {
+        let bytes: [u8; 3] = [0b10001001, 0b11000011, 0b10111000];
+        assert_eq!(
+            try_decode_8086(&bytes[..]),
+            Err(DecodeError::new(
+                DecodeErrorKind::TruncatedOperand,
+                2,
+                &[0b10111000]
+            ))
+        );
+        let bytes: [u8; 2] = [0b00101110, 0b11111111];
+        assert_eq!(
+            try_decode_8086(&bytes[..]),
+            Err(DecodeError::new(
+                DecodeErrorKind::TruncatedOperand,
+                0,
+                &[0b00101110, 0b11111111]
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_error_dangling_prefix() {
+        let bytes: [u8; 3] = [0b10001001, 0b11000011, 0b11110000];
+        assert_eq!(
+            try_decode_8086(&bytes[..]),
+            Err(DecodeError::new(
+                DecodeErrorKind::DanglingPrefix,
+                2,
+                &[0b11110000]
+            ))
+        );
+    }
 }
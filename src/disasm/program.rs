@@ -1,9 +1,12 @@
+use std::fmt;
+
+use super::encode;
 use crate::{
-    fields::{Operand, Operation},
+    fields::{Operand, Operation, SegmentRegister},
     instruction::{Inst, InstructionPrefix},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Instruction {
     pub operation: Operation,
     pub first: Option<Operand>,
@@ -12,6 +15,29 @@ pub struct Instruction {
     pub size: usize,
 }
 
+impl Instruction {
+    pub fn segment_override(&self) -> Option<SegmentRegister> {
+        self.prefix.and_then(InstructionPrefix::segment_override)
+    }
+}
+
+impl From<&Instruction> for Inst {
+    fn from(value: &Instruction) -> Self {
+        let mut inst = Inst::new(value.operation);
+        inst.first = value.first;
+        inst.second = value.second;
+        inst.prefix = value.prefix;
+        inst.set_size(value.size);
+        inst
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Inst::from(self))
+    }
+}
+
 impl TryFrom<Inst> for Instruction {
     type Error = ();
     fn try_from(value: Inst) -> Result<Self, Self::Error> {
@@ -29,50 +55,41 @@ impl TryFrom<Inst> for Instruction {
     }
 }
 
+/// Machine code to be loaded into the simulator's memory
+#[derive(Debug, Default, Clone)]
 pub struct Program {
-    ip: usize,
-    instructions: Vec<Instruction>,
+    bytes: Vec<u8>,
 }
 
 impl Program {
-    pub fn next_instruction(&mut self) -> Option<&Instruction> {
-        let instruction = self.instructions.get(self.ip);
-        if instruction.is_some() {
-            self.ip += 1;
-        }
-        instruction
-    }
-
-    /// returns size of the previous instruction in bytes
-    fn go_back(&mut self) -> Option<usize> {
-        let instruction = self.instructions.get(self.ip - 1);
-        instruction.map(|i| {
-            self.ip -= 1;
-            i.size
-        })
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
     }
+}
 
-    pub fn advance_by(&mut self, mut nbytes: i16) {
-        while nbytes != 0 {
-            if nbytes.is_positive() {
-                nbytes -= self.next_instruction().expect("jmp within code range").size as i16;
-            } else {
-                nbytes += self.go_back().expect("jmp within code range") as i16;
-            }
-        }
+impl From<Vec<u8>> for Program {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self { bytes }
     }
 }
 
 impl TryFrom<Vec<Inst>> for Program {
     type Error = ();
+    /// Lays the instructions out back to back, using the bytes they were decoded or assembled
+    /// from, or encoding them if they have none
     fn try_from(value: Vec<Inst>) -> Result<Self, Self::Error> {
-        let instructions: Vec<Instruction> = value
-            .into_iter()
-            .map(|x| x.try_into())
-            .collect::<Result<Vec<Instruction>, ()>>()?;
-        Ok(Self {
-            ip: 0,
-            instructions,
-        })
+        let mut bytes = Vec::new();
+        for inst in value {
+            if !inst.bytes().is_empty() {
+                bytes.extend(inst.bytes());
+            } else {
+                let encoded = encode(&inst).map_err(|_| ())?;
+                if inst.size().is_some_and(|size| size != encoded.len()) {
+                    return Err(());
+                }
+                bytes.extend(encoded);
+            }
+        }
+        Ok(Self { bytes })
     }
 }
@@ -0,0 +1,178 @@
+use std::io::{self, Read};
+
+use super::{decode_next, DecodeError, DecodeErrorKind};
+use crate::instruction::Inst;
+
+/// Longest instruction the stream decoder buffers: 6 bytes plus one each of the LOCK, REP and
+/// segment override prefixes
+pub const MAX_INSTRUCTION_LEN: usize = 9;
+const READ_CHUNK: usize = 4096;
+
+#[derive(Debug, PartialEq)]
+pub struct DecodedInst {
+    /// byte offset of the instruction (including its prefixes) in the stream
+    pub offset: usize,
+    pub inst: Inst,
+}
+
+/// Decodes instructions lazily from any reader, holding only a small window of the input in
+/// memory. Stops after the first error, including an instruction longer than
+/// `MAX_INSTRUCTION_LEN`.
+pub struct Decoder<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    pos: usize,
+    offset: usize,
+    eof: bool,
+    failed: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::with_capacity(READ_CHUNK),
+            pos: 0,
+            offset: 0,
+            eof: false,
+            failed: false,
+        }
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.buffer[self.pos..]
+    }
+
+    /// Reads until at least `wanted` bytes are pending or the reader is exhausted
+    fn fill(&mut self, wanted: usize) -> io::Result<()> {
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+        let mut chunk = [0u8; READ_CHUNK];
+        while !self.eof && self.buffer.len() < wanted {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, mut err: DecodeError) -> Option<Result<DecodedInst, DecodeError>> {
+        self.failed = true;
+        err.offset += self.offset;
+        Some(Err(err))
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = Result<DecodedInst, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.pending().len() < MAX_INSTRUCTION_LEN {
+            if let Err(err) = self.fill(MAX_INSTRUCTION_LEN) {
+                let kind = DecodeErrorKind::Io(err.kind());
+                return self.fail(DecodeError::new(kind, 0, &[]));
+            }
+        }
+        if self.pending().is_empty() {
+            return None;
+        }
+        let window = &self.pending()[..self.pending().len().min(MAX_INSTRUCTION_LEN)];
+        match decode_next(window, 0) {
+            Ok(inst) => {
+                let size = inst.size().expect("decoded instruction has size");
+                let offset = self.offset;
+                self.pos += size;
+                self.offset += size;
+                Some(Ok(DecodedInst { offset, inst }))
+            }
+            // running out of the window isn't the end of the input
+            Err(err)
+                if (!self.eof || self.pending().len() > MAX_INSTRUCTION_LEN)
+                    && matches!(
+                        err.kind,
+                        DecodeErrorKind::TruncatedOperand | DecodeErrorKind::DanglingPrefix
+                    ) =>
+            {
+                self.fail(DecodeError::new(DecodeErrorKind::TooLong, 0, &err.bytes))
+            }
+            Err(err) => self.fail(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::decode_8086;
+
+    /// Hands out the input one byte per read call to exercise buffer refills
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn matches_slice_decoder() {
+        let mut bytes = vec![0b10111001, 0b00000011, 0b00000000];
+        bytes.extend([0b11110000, 0b11110011, 0b00101110]);
+        bytes.extend([0b10001011, 0b00011110, 0b00000001, 0b00000000, 0b01001001]);
+
+        let decoded: Vec<DecodedInst> = Decoder::new(Trickle(&bytes))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let offsets: Vec<usize> = decoded.iter().map(|d| d.offset).collect();
+        assert_eq!(offsets, [0, 3, 10]);
+        let instructions: Vec<Inst> = decoded.into_iter().map(|d| d.inst).collect();
+        assert_eq!(instructions, decode_8086(&bytes));
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let bytes: [u8; 5] = [0b10001001, 0b11000011, 0b01100000, 0b10001001, 0b11000011];
+        let mut decoder = Decoder::new(Trickle(&bytes));
+        assert!(decoder.next().unwrap().is_ok());
+        assert_eq!(
+            decoder.next(),
+            Some(Err(DecodeError::new(
+                DecodeErrorKind::UnknownOpcode,
+                2,
+                &[0b01100000]
+            )))
+        );
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn rejects_overlong_prefix_runs() {
+        let mut bytes = vec![0b01001001];
+        bytes.extend([0b00101110; 20]);
+        bytes.push(0b01001001);
+        let mut decoder = Decoder::new(bytes.as_slice());
+        assert!(decoder.next().unwrap().is_ok());
+        assert_eq!(
+            decoder.next(),
+            Some(Err(DecodeError::new(
+                DecodeErrorKind::TooLong,
+                1,
+                &[0b00101110; MAX_INSTRUCTION_LEN]
+            )))
+        );
+        assert_eq!(decoder.next(), None);
+    }
+}
@@ -22,33 +22,60 @@ pub struct Carry(pub bool);
 pub struct HalfCarry(pub bool);
 pub struct DataWithCarry(pub Data, pub Carry, pub HalfCarry);
 
+impl Data {
+    fn width_mask(self) -> u32 {
+        match self {
+            Data::U8(_) => 0xff,
+            Data::U16(_) => 0xffff,
+        }
+    }
+
+    fn with_value(self, value: u32) -> Data {
+        match self {
+            Data::U8(_) => Data::U8(value as u8),
+            Data::U16(_) => Data::U16(value as u16),
+        }
+    }
+
+    /// `self + rhs + carry_in`, the way ADC computes it
+    pub fn add_with_carry(self, rhs: Data, carry_in: bool) -> DataWithCarry {
+        assert_eq!(
+            self.width_mask(),
+            rhs.width_mask(),
+            "operands of same width"
+        );
+        let (x, y, c) = (self.to_u16() as u32, rhs.to_u16() as u32, carry_in as u32);
+        let sum = x + y + c;
+        let half_carry = (x & 0xf) + (y & 0xf) + c > 0xf;
+        DataWithCarry(
+            self.with_value(sum),
+            Carry(sum > self.width_mask()),
+            HalfCarry(half_carry),
+        )
+    }
+
+    /// `self - rhs - borrow_in`, the way SBB computes it
+    pub fn sub_with_borrow(self, rhs: Data, borrow_in: bool) -> DataWithCarry {
+        assert_eq!(
+            self.width_mask(),
+            rhs.width_mask(),
+            "operands of same width"
+        );
+        let (x, y, c) = (self.to_u16() as u32, rhs.to_u16() as u32, borrow_in as u32);
+        let half_carry = (x & 0xf) < (y & 0xf) + c;
+        DataWithCarry(
+            self.with_value(x.wrapping_sub(y + c)),
+            Carry(x < y + c),
+            HalfCarry(half_carry),
+        )
+    }
+}
+
 impl ops::Add<Data> for Data {
     type Output = DataWithCarry;
 
     fn add(self, rhs: Data) -> Self::Output {
-        const NIBBLE_MAX: u8 = 15;
-        match self {
-            Self::U8(x) => match rhs {
-                Self::U8(y) => {
-                    let (val, carry) = x.overflowing_add(y);
-                    let xnibble = (x << 4) >> 4;
-                    let ynibble = (y << 4) >> 4;
-                    let half_carry = xnibble + ynibble > NIBBLE_MAX;
-                    DataWithCarry(Data::U8(val), Carry(carry), HalfCarry(half_carry))
-                }
-                Self::U16(_) => unreachable!(),
-            },
-            Self::U16(x) => match rhs {
-                Self::U8(_) => unreachable!(),
-                Self::U16(y) => {
-                    let (val, carry) = x.overflowing_add(y);
-                    let xnibble = ((x << 12) >> 12) as u8;
-                    let ynibble = ((y << 12) >> 12) as u8;
-                    let half_carry = xnibble + ynibble > NIBBLE_MAX;
-                    DataWithCarry(Data::U16(val), Carry(carry), HalfCarry(half_carry))
-                }
-            },
-        }
+        self.add_with_carry(rhs, false)
     }
 }
 
@@ -56,28 +83,7 @@ impl ops::Sub<Data> for Data {
     type Output = DataWithCarry;
 
     fn sub(self, rhs: Data) -> Self::Output {
-        match self {
-            Self::U8(x) => match rhs {
-                Self::U8(y) => {
-                    let (val, carry) = x.overflowing_sub(y);
-                    let xnibble = (x << 4) >> 4;
-                    let ynibble = (y << 4) >> 4;
-                    let half_carry = xnibble < ynibble;
-                    DataWithCarry(Data::U8(val), Carry(carry), HalfCarry(half_carry))
-                }
-                Self::U16(_) => unreachable!(),
-            },
-            Self::U16(x) => match rhs {
-                Self::U8(_) => unreachable!(),
-                Self::U16(y) => {
-                    let (val, carry) = x.overflowing_sub(y);
-                    let xnibble = ((x << 12) >> 12) as u8;
-                    let ynibble = ((y << 12) >> 12) as u8;
-                    let half_carry = xnibble < ynibble;
-                    DataWithCarry(Data::U16(val), Carry(carry), HalfCarry(half_carry))
-                }
-            },
-        }
+        self.sub_with_borrow(rhs, false)
     }
 }
 
@@ -113,22 +119,32 @@ impl ops::BitXor<Data> for Data {
     }
 }
 
-impl ops::Shr<Data> for Data {
+impl ops::BitOr<Data> for Data {
     type Output = Data;
-    fn shr(self, rhs: Data) -> Self::Output {
+    fn bitor(self, rhs: Data) -> Self::Output {
         match self {
             Self::U8(x) => match rhs {
-                Self::U8(y) => Data::U8(x >> y),
+                Self::U8(y) => Data::U8(x | y),
                 Self::U16(_) => unreachable!(),
             },
             Self::U16(x) => match rhs {
-                Self::U8(y) => Data::U16(x >> y),
-                Self::U16(_) => unreachable!(),
+                Self::U8(_) => unreachable!(),
+                Self::U16(y) => Data::U16(x | y),
             },
         }
     }
 }
 
+impl ops::Not for Data {
+    type Output = Data;
+    fn not(self) -> Self::Output {
+        match self {
+            Self::U8(x) => Data::U8(!x),
+            Self::U16(x) => Data::U16(!x),
+        }
+    }
+}
+
 impl From<Data> for Operand {
     fn from(val: Data) -> Self {
         Operand::Immediate(val)
@@ -109,11 +109,31 @@ pub enum Operation {
 
     // instruction prefixes
     Lock,
-    Rep,
+    RepE,
+    RepNE,
     SegmentOverrideES,
     SegmentOverrideCS,
     SegmentOverrideSS,
     SegmentOverrideDS,
+
+    // pseudo instructions
+    DB,
+}
+
+impl Operation {
+    /// MOVS, LODS, STOS, CMPS and SCAS, which repeat under a REP prefix
+    pub fn is_string(&self) -> bool {
+        self.is_string_compare()
+            || matches!(
+                self,
+                Self::MOVSB | Self::MOVSW | Self::LODSB | Self::LODSW | Self::STOSB | Self::STOSW
+            )
+    }
+
+    /// CMPS and SCAS, which REPE and REPNE also stop on ZF
+    pub fn is_string_compare(&self) -> bool {
+        matches!(self, Self::CMPSB | Self::CMPSW | Self::SCASB | Self::SCASW)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -41,3 +41,17 @@ pub fn register_from_u8(reg: u8, wide: bool) -> Register {
         _ => unreachable!()
     }
 }
+
+#[rustfmt::skip]
+pub fn register_to_u8(reg: Register) -> u8 {
+    match reg {
+        Register::AX | Register::AL => 0b000,
+        Register::CX | Register::CL => 0b001,
+        Register::DX | Register::DL => 0b010,
+        Register::BX | Register::BL => 0b011,
+        Register::SP | Register::AH => 0b100,
+        Register::BP | Register::CH => 0b101,
+        Register::SI | Register::DH => 0b110,
+        Register::DI | Register::BH => 0b111,
+    }
+}
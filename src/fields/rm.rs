@@ -28,4 +28,4 @@ impl fmt::Display for RM {
             }
         )
     }
-}
\ No newline at end of file
+}
@@ -26,3 +26,12 @@ pub fn sr_from_u8(sr: u8) -> SegmentRegister {
         _ => unreachable!(),
     }
 }
+
+pub fn sr_to_u8(sr: SegmentRegister) -> u8 {
+    match sr {
+        SegmentRegister::ES => 0b00,
+        SegmentRegister::CS => 0b01,
+        SegmentRegister::SS => 0b10,
+        SegmentRegister::DS => 0b11,
+    }
+}
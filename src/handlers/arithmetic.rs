@@ -3,23 +3,28 @@ use enum_stringify::EnumStringify;
 use crate::{
     cpu::{Flags, Memory, Registers},
     disasm::Instruction,
-    fields::{Data, DataWithCarry, Operand, Operation, Wide},
+    fields::{Data, DataWithCarry, Operand, Operation},
+    simulator::SimErrorKind,
 };
 
 #[derive(EnumStringify, PartialEq)]
 #[enum_stringify(case = "lower")]
 pub enum ArithmeticOp {
     Add,
+    Adc,
     Sub,
+    Sbb,
     Cmp,
     Inc,
     Dec,
+    Neg,
 }
 
 impl ArithmeticOp {
-    fn compute(&self, lhs: Data, rhs: Data) -> DataWithCarry {
+    fn compute(&self, lhs: Data, rhs: Data, carry_in: bool) -> DataWithCarry {
         match self {
             Self::Add => lhs + rhs,
+            Self::Adc => lhs.add_with_carry(rhs, carry_in),
             Self::Inc => {
                 lhs + match lhs {
                     Data::U16(_) => Data::U16(1),
@@ -33,7 +38,14 @@ impl ArithmeticOp {
                 }
             }
             Self::Sub => lhs - rhs,
+            Self::Sbb => lhs.sub_with_borrow(rhs, carry_in),
             Self::Cmp => lhs - rhs,
+            Self::Neg => {
+                (match lhs {
+                    Data::U16(_) => Data::U16(0),
+                    Data::U8(_) => Data::U8(0),
+                }) - lhs
+            }
         }
     }
 }
@@ -44,26 +56,29 @@ pub fn handle_arithmetic(
     registers: &mut Registers,
     flags: &mut Flags,
     memory: &mut Memory,
-) {
+) -> Result<(), SimErrorKind> {
     let first = inst
         .first
-        .unwrap_or_else(|| panic!("{} has first operand", op));
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
     let second = inst
         .second
         .or({
-            if matches!(inst.operation, Operation::INC | Operation::DEC) {
+            if matches!(
+                inst.operation,
+                Operation::INC | Operation::DEC | Operation::NEG
+            ) {
                 // This is a dummy value. RHS is overriden based on first operand width in compute fn
                 Some(Operand::Immediate(Data::U16(1)))
             } else {
                 None
             }
         })
-        .unwrap_or_else(|| panic!("{} has second operand", op));
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
 
     match (first, second) {
         (Operand::Register(reg), Operand::Immediate(rhs)) => {
             let lhs = registers.get(reg);
-            let newval = op.compute(lhs, rhs);
+            let newval = op.compute(lhs, rhs, flags.carry);
             if op != ArithmeticOp::Cmp {
                 registers.set_imd(reg, newval.0);
             }
@@ -72,52 +87,41 @@ pub fn handle_arithmetic(
         (Operand::Register(reg1), Operand::Register(reg2)) => {
             let lhs = registers.get(reg1);
             let rhs = registers.get(reg2);
-            let newval = op.compute(lhs, rhs);
+            let newval = op.compute(lhs, rhs, flags.carry);
             if op != ArithmeticOp::Cmp {
                 registers.set_imd(reg1, newval.0);
             }
             flags.set(lhs, rhs, op, newval);
         }
         (Operand::Register(reg), Operand::EffectiveAddress(ea)) => {
-            let addr = registers.calculate_eff_addr(ea);
-            let rhs = match ea.wide() {
-                Wide::Word => Data::U16(memory.load_16(addr)),
-                Wide::Byte => Data::U8(memory.load_8(addr)),
-                _ => unreachable!(),
-            };
+            let addr = registers.effective_address(ea, inst.segment_override());
+            let rhs = memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
             let lhs = registers.get(reg);
-            let newval = op.compute(lhs, rhs);
+            let newval = op.compute(lhs, rhs, flags.carry);
             if op != ArithmeticOp::Cmp {
                 registers.set_imd(reg, newval.0);
             }
             flags.set(lhs, rhs, op, newval);
         }
-        (Operand::EffectiveAddress(ea), Operand::Register(reg)) => {
-            if ea.wide() == Wide::Byte {
-                unimplemented!()
-            }
-            let addr = registers.calculate_eff_addr(ea);
-            let lhs = Data::U16(memory.load_16(addr));
-            let rhs = registers.get(reg);
-            let newval = op.compute(lhs, rhs);
-            if op != ArithmeticOp::Cmp {
-                memory.store_16(addr, newval.0.into());
-            }
-            flags.set(lhs, rhs, op, newval);
-        }
-        (Operand::EffectiveAddress(ea), Operand::Immediate(imd)) => {
-            if ea.wide() == Wide::Byte {
-                unimplemented!()
-            }
-            let addr = registers.calculate_eff_addr(ea);
-            let lhs = Data::U16(memory.load_16(addr));
-            let rhs = imd;
-            let newval = op.compute(lhs, rhs);
+        (Operand::EffectiveAddress(ea), Operand::Register(_) | Operand::Immediate(_)) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            let lhs = memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+            let rhs = match second {
+                Operand::Register(reg) => registers.get(reg),
+                Operand::Immediate(imd) => imd,
+                _ => unreachable!(),
+            };
+            let newval = op.compute(lhs, rhs, flags.carry);
             if op != ArithmeticOp::Cmp {
-                memory.store_16(addr, newval.0.into());
+                memory.store_data(addr, newval.0);
             }
             flags.set(lhs, rhs, op, newval);
         }
-        _ => unimplemented!("{:?}", inst),
+        _ => return Err(SimErrorKind::unsupported(inst)),
     }
+    Ok(())
 }
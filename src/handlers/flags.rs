@@ -0,0 +1,39 @@
+use crate::{
+    cpu::{Flags, Memory, Registers},
+    disasm::Instruction,
+    fields::{Data, Operation, Register},
+    simulator::SimErrorKind,
+};
+
+use super::{pop, push};
+
+/// Instructions that work on the flags themselves: PUSHF, POPF, LAHF, SAHF and the set and
+/// clear instructions
+pub fn handle_flags(
+    inst: &Instruction,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    match inst.operation {
+        Operation::PUSHF => push(registers, memory, flags.to_u16()),
+        Operation::POPF => *flags = Flags::from_u16(pop(registers, memory)),
+        Operation::LAHF => {
+            registers.set_imd(Register::AH, Data::U8(flags.to_u16() as u8));
+        }
+        Operation::SAHF => {
+            let ah = u8::try_from(&registers.get(Register::AH)).expect("8bit register");
+            // AH only covers SF, ZF, AF, PF and CF
+            *flags = Flags::from_u16(flags.to_u16() & 0xff00 | ah as u16);
+        }
+        Operation::CLC => flags.carry = false,
+        Operation::STC => flags.carry = true,
+        Operation::CMC => flags.carry = !flags.carry,
+        Operation::CLD => flags.direction = false,
+        Operation::STD => flags.direction = true,
+        Operation::CLI => flags.interrupt = false,
+        Operation::STI => flags.interrupt = true,
+        _ => return Err(SimErrorKind::unsupported(inst)),
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::{
+    cpu::{Address, Flags, Memory, Registers},
+    fields::{Data, SegmentRegister},
+};
+
+use super::{pop, push};
+
+/// Entry for `vector` in the interrupt vector table at 0000:0000, IP first and then CS
+pub fn interrupt_vector(memory: &Memory, vector: u8) -> Address {
+    let entry = Address::new(0, vector as u16 * 4);
+    Address::new(memory.load_16(entry.offset_by(2)), memory.load_16(entry))
+}
+
+/// Pushes FLAGS, CS and IP, clears IF and TF and jumps to the handler at `target`
+pub fn enter_interrupt(
+    target: Address,
+    ip: &mut u16,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) {
+    push(registers, memory, flags.to_u16());
+    let cs = registers.cs();
+    push(registers, memory, cs);
+    push(registers, memory, *ip);
+    flags.interrupt = false;
+    flags.trap = false;
+    registers.set_sr_imd(SegmentRegister::CS, Data::U16(target.segment));
+    *ip = target.offset;
+}
+
+/// Pops IP, CS and FLAGS pushed on entering an interrupt
+pub fn handle_iret(ip: &mut u16, registers: &mut Registers, flags: &mut Flags, memory: &Memory) {
+    *ip = pop(registers, memory);
+    let cs = pop(registers, memory);
+    registers.set_sr_imd(SegmentRegister::CS, Data::U16(cs));
+    *flags = Flags::from_u16(pop(registers, memory));
+}
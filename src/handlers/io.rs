@@ -0,0 +1,40 @@
+use crate::{
+    cpu::Registers,
+    disasm::Instruction,
+    fields::{Data, Operand, Operation, Register},
+    simulator::{IoBus, SimErrorKind},
+};
+
+/// IN and OUT through AL or AX, on a fixed port or the one in DX
+pub fn handle_io(
+    inst: &Instruction,
+    registers: &mut Registers,
+    bus: &mut dyn IoBus,
+) -> Result<(), SimErrorKind> {
+    let (acc, port) = match inst.operation {
+        Operation::IN => (inst.first, inst.second),
+        _ => (inst.second, inst.first),
+    };
+    let port = match port {
+        Some(Operand::Immediate(Data::U8(port))) => port as u16,
+        Some(Operand::Register(Register::DX)) => registers.get(Register::DX).into(),
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    match (inst.operation, acc) {
+        (Operation::IN, Some(Operand::Register(Register::AL))) => {
+            registers.set_imd(Register::AL, Data::U8(bus.read_u8(port)))
+        }
+        (Operation::IN, Some(Operand::Register(Register::AX))) => {
+            registers.set_imd(Register::AX, Data::U16(bus.read_u16(port)))
+        }
+        (Operation::OUT, Some(Operand::Register(Register::AL))) => {
+            let al = u8::try_from(&registers.get(Register::AL)).expect("8bit register");
+            bus.write_u8(port, al)
+        }
+        (Operation::OUT, Some(Operand::Register(Register::AX))) => {
+            bus.write_u16(port, registers.get(Register::AX).into())
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    }
+    Ok(())
+}
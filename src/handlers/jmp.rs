@@ -0,0 +1,41 @@
+use crate::{
+    cpu::{Memory, Registers},
+    disasm::Instruction,
+    fields::{Data, Operand, Operation, SegmentRegister},
+    simulator::SimErrorKind,
+};
+
+use super::read_word;
+
+/// Unconditional jumps: relative, through a register or memory word, and far either direct or
+/// through a doubleword in memory. `ip` already points past the jump.
+pub fn handle_jmp(
+    inst: &Instruction,
+    ip: &mut u16,
+    registers: &mut Registers,
+    memory: &Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let (cs, target) = match (inst.operation, first) {
+        (Operation::Jmp, Operand::Increment(inc)) => (None, ip.wrapping_add_signed(i16::from(inc))),
+        (Operation::Jmp, Operand::CsIp(target)) => {
+            (Some(target.code_segment), target.instruction_pointer)
+        }
+        (Operation::Jmp, operand) => (None, read_word(inst, operand, registers, memory)?),
+        (Operation::JmpFar, Operand::EffectiveAddress(ea)) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            (
+                Some(memory.load_16(addr.offset_by(2))),
+                memory.load_16(addr),
+            )
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    if let Some(cs) = cs {
+        registers.set_sr_imd(SegmentRegister::CS, Data::U16(cs));
+    }
+    *ip = target;
+    Ok(())
+}
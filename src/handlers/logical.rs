@@ -4,58 +4,80 @@ use crate::{
     cpu::{Flags, Memory, Registers},
     disasm::Instruction,
     fields::{Data, Operand},
+    simulator::SimErrorKind,
 };
 
 #[derive(EnumStringify, PartialEq)]
 #[enum_stringify(case = "lower")]
 pub enum LogicalOp {
-    Test,
+    And,
+    Or,
     Xor,
-    Shr,
+    Not,
+    Test,
 }
 
 impl LogicalOp {
     fn compute(&self, lhs: Data, rhs: Data) -> Data {
         match self {
-            Self::Test => lhs & rhs,
+            Self::And | Self::Test => lhs & rhs,
+            Self::Or => lhs | rhs,
             Self::Xor => lhs ^ rhs,
-            Self::Shr => lhs >> rhs,
+            Self::Not => !lhs,
         }
     }
 }
 
+fn read(
+    operand: Operand,
+    inst: &Instruction,
+    registers: &Registers,
+    memory: &Memory,
+) -> Result<Data, SimErrorKind> {
+    Ok(match operand {
+        Operand::Register(reg) => registers.get(reg),
+        Operand::Immediate(imd) => imd,
+        Operand::EffectiveAddress(ea) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    })
+}
+
 pub fn handle_logical(
     op: LogicalOp,
     inst: &Instruction,
     registers: &mut Registers,
     flags: &mut Flags,
-    _memory: &mut Memory,
-) {
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
     let first = inst
         .first
-        .unwrap_or_else(|| panic!("{} has first operand", op));
-    let second = inst
-        .second
-        .unwrap_or_else(|| panic!("{} has second operand", op));
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let lhs = read(first, inst, registers, memory)?;
+    let rhs = match inst.second {
+        Some(second) => read(second, inst, registers, memory)?,
+        None if op == LogicalOp::Not => lhs,
+        None => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    let newval = op.compute(lhs, rhs);
 
-    match (first, second) {
-        (Operand::Register(reg1), Operand::Register(reg2)) => {
-            let lhs = registers.get(reg1);
-            let rhs = registers.get(reg2);
-            let newval = op.compute(lhs, rhs);
-            if op != LogicalOp::Test {
-                registers.set_imd(reg1, newval);
-            }
-            flags.set_logical(newval);
-        }
-        (Operand::Register(reg), Operand::Immediate(rhs)) => {
-            let lhs = registers.get(reg);
-            let newval = op.compute(lhs, rhs);
-            if op != LogicalOp::Test {
-                registers.set_imd(reg, newval);
+    if op != LogicalOp::Test {
+        match first {
+            Operand::Register(reg) => registers.set_imd(reg, newval),
+            Operand::EffectiveAddress(ea) => {
+                let addr = registers.effective_address(ea, inst.segment_override());
+                memory.store_data(addr, newval)
             }
-            flags.set_logical(newval);
+            _ => return Err(SimErrorKind::invalid_operands(inst)),
         }
-        _ => unimplemented!("{:?}", inst),
     }
+    // NOT leaves every flag alone
+    if op != LogicalOp::Not {
+        flags.set_logical(newval);
+    }
+    Ok(())
 }
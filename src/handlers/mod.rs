@@ -1,7 +1,23 @@
 mod arithmetic;
 mod conditional_jmp;
+mod flags;
+mod interrupt;
+mod io;
+mod jmp;
 mod logical;
 mod mov;
+mod multiply;
+mod shift;
+mod stack;
+mod string;
 pub use arithmetic::*;
+pub use flags::*;
+pub use interrupt::*;
+pub use io::*;
+pub use jmp::*;
 pub use logical::*;
 pub use mov::*;
+pub use multiply::*;
+pub use shift::*;
+pub use stack::*;
+pub use string::*;
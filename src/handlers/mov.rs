@@ -1,43 +1,61 @@
 use crate::{
     cpu::{Memory, Registers},
     disasm::Instruction,
-    fields::{Data, Operand, Wide},
+    fields::{Data, Operand},
+    simulator::SimErrorKind,
 };
 
-pub fn handle_mov(inst: &Instruction, registers: &mut Registers, memory: &mut Memory) {
-    let first = inst.first.expect("mov has first operand");
-    let second = inst.second.expect("mov has second operand");
+pub fn handle_mov(
+    inst: &Instruction,
+    registers: &mut Registers,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let second = inst
+        .second
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
 
     match (first, second) {
         (Operand::Register(reg), Operand::Immediate(data)) => registers.set_imd(reg, data),
         (Operand::Register(reg1), Operand::Register(reg2)) => registers.set_reg(reg1, reg2),
         (Operand::Register(reg), Operand::SR(sr)) => registers.set_reg_from_sr(reg, sr),
         (Operand::SR(sr), Operand::Register(reg)) => registers.set_sr_from_reg(sr, reg),
-        (Operand::EffectiveAddress(addr), Operand::Immediate(Data::U16(imd))) => {
-            memory.store_16(registers.calculate_eff_addr(addr), imd);
-        }
-        (Operand::EffectiveAddress(addr), Operand::Immediate(Data::U8(imd))) => {
-            memory.store_8(registers.calculate_eff_addr(addr), imd);
+        (Operand::EffectiveAddress(ea), Operand::Immediate(imd)) => {
+            memory.store_data(
+                registers.effective_address(ea, inst.segment_override()),
+                imd,
+            );
         }
         (Operand::Register(reg), Operand::EffectiveAddress(ea)) => {
-            if ea.wide() == Wide::Byte {
-                unimplemented!()
-            }
-            let addr = registers.calculate_eff_addr(ea);
-            let imd = memory.load_16(addr);
-            registers.set_imd(reg, Data::U16(imd));
+            let addr = registers.effective_address(ea, inst.segment_override());
+            let data = memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+            registers.set_imd(reg, data);
         }
         (Operand::EffectiveAddress(ea), Operand::Register(reg)) => {
             let data = registers.get(reg);
-            let addr = registers.calculate_eff_addr(ea);
-            match ea.wide() {
-                Wide::Byte => memory.store_8(addr, (&data).try_into().expect("8bit data")),
-                Wide::Word => memory.store_16(addr, data.into()),
-                _ => unreachable!(),
-            }
+            memory.store_data(
+                registers.effective_address(ea, inst.segment_override()),
+                data,
+            );
+        }
+        (Operand::SR(sr), Operand::EffectiveAddress(ea)) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            registers.set_sr_imd(sr, Data::U16(memory.load_16(addr)));
+        }
+        (Operand::EffectiveAddress(ea), Operand::SR(sr)) => {
+            let data = registers.get_sr(sr);
+            memory.store_data(
+                registers.effective_address(ea, inst.segment_override()),
+                data,
+            );
         }
-        _ => unimplemented!("{:?}", inst),
+        _ => return Err(SimErrorKind::unsupported(inst)),
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -57,7 +75,7 @@ mod tests {
         };
         let mut registers = Registers::default();
         let mut memory = Memory::default();
-        handle_mov(&inst, &mut registers, &mut memory);
+        handle_mov(&inst, &mut registers, &mut memory).unwrap();
         assert_eq!(registers.get(Register::BX), Data::U16(256));
     }
 }
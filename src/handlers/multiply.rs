@@ -0,0 +1,122 @@
+use enum_stringify::EnumStringify;
+
+use crate::{
+    cpu::{Flags, Memory, Registers},
+    disasm::Instruction,
+    fields::{Data, Operand, Register},
+    simulator::SimErrorKind,
+};
+
+#[derive(EnumStringify, PartialEq, Clone, Copy)]
+#[enum_stringify(case = "lower")]
+pub enum MultiplyOp {
+    Mul,
+    Imul,
+    Div,
+    Idiv,
+}
+
+/// MUL, IMUL, DIV and IDIV with AL/AX or AX/DX:AX as the implicit operand. Flags left undefined by
+/// the 8086 manual are kept as they were.
+pub fn handle_multiply(
+    op: MultiplyOp,
+    inst: &Instruction,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let src = match first {
+        Operand::Register(reg) => registers.get(reg),
+        Operand::EffectiveAddress(ea) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    let ax = u16::from(registers.get(Register::AX));
+    let dx = u16::from(registers.get(Register::DX));
+
+    match (op, src) {
+        (MultiplyOp::Mul, Data::U8(src)) => {
+            let product = (ax as u8 as u16) * src as u16;
+            registers.set_imd(Register::AX, Data::U16(product));
+            flags.carry = product > 0xff;
+        }
+        (MultiplyOp::Mul, Data::U16(src)) => {
+            let product = ax as u32 * src as u32;
+            set_dx_ax(registers, product);
+            flags.carry = product > 0xffff;
+        }
+        (MultiplyOp::Imul, Data::U8(src)) => {
+            let product = (ax as u8 as i8 as i16) * (src as i8 as i16);
+            registers.set_imd(Register::AX, Data::U16(product as u16));
+            flags.carry = i8::try_from(product).is_err();
+        }
+        (MultiplyOp::Imul, Data::U16(src)) => {
+            let product = (ax as i16 as i32) * (src as i16 as i32);
+            set_dx_ax(registers, product as u32);
+            flags.carry = i16::try_from(product).is_err();
+        }
+        (MultiplyOp::Div, Data::U8(src)) => {
+            let (quotient, remainder) = divide(ax as u32, src as u32)?;
+            let quotient = u8::try_from(quotient).map_err(|_| SimErrorKind::DivideError)?;
+            registers.set_imd(Register::AL, Data::U8(quotient));
+            registers.set_imd(Register::AH, Data::U8(remainder as u8));
+        }
+        (MultiplyOp::Div, Data::U16(src)) => {
+            let dividend = (dx as u32) << 16 | ax as u32;
+            let (quotient, remainder) = divide(dividend, src as u32)?;
+            let quotient = u16::try_from(quotient).map_err(|_| SimErrorKind::DivideError)?;
+            registers.set_imd(Register::AX, Data::U16(quotient));
+            registers.set_imd(Register::DX, Data::U16(remainder as u16));
+        }
+        (MultiplyOp::Idiv, Data::U8(src)) => {
+            let (quotient, remainder) = signed_divide(ax as i16 as i64, src as i8 as i64)?;
+            // the 8086 faults on the most negative quotient too
+            if !(-127..=127).contains(&quotient) {
+                return Err(SimErrorKind::DivideError);
+            }
+            registers.set_imd(Register::AL, Data::U8(quotient as u8));
+            registers.set_imd(Register::AH, Data::U8(remainder as u8));
+        }
+        (MultiplyOp::Idiv, Data::U16(src)) => {
+            let dividend = ((dx as u32) << 16 | ax as u32) as i32;
+            let (quotient, remainder) = signed_divide(dividend as i64, src as i16 as i64)?;
+            if !(-32767..=32767).contains(&quotient) {
+                return Err(SimErrorKind::DivideError);
+            }
+            registers.set_imd(Register::AX, Data::U16(quotient as u16));
+            registers.set_imd(Register::DX, Data::U16(remainder as u16));
+        }
+    }
+    if matches!(op, MultiplyOp::Mul | MultiplyOp::Imul) {
+        // set when the upper half of the product is significant
+        flags.overflow = flags.carry;
+    }
+    Ok(())
+}
+
+fn set_dx_ax(registers: &mut Registers, value: u32) {
+    registers.set_imd(Register::AX, Data::U16(value as u16));
+    registers.set_imd(Register::DX, Data::U16((value >> 16) as u16));
+}
+
+fn divide(dividend: u32, divisor: u32) -> Result<(u32, u32), SimErrorKind> {
+    if divisor == 0 {
+        return Err(SimErrorKind::DivideError);
+    }
+    Ok((dividend / divisor, dividend % divisor))
+}
+
+/// Quotient truncated towards zero, remainder with the sign of the dividend
+fn signed_divide(dividend: i64, divisor: i64) -> Result<(i64, i64), SimErrorKind> {
+    if divisor == 0 {
+        return Err(SimErrorKind::DivideError);
+    }
+    Ok((dividend / divisor, dividend % divisor))
+}
@@ -0,0 +1,115 @@
+use enum_stringify::EnumStringify;
+
+use crate::{
+    cpu::{Flags, Memory, Registers},
+    disasm::Instruction,
+    fields::{Data, Operand, Register},
+    simulator::SimErrorKind,
+};
+
+#[derive(EnumStringify, PartialEq, Clone, Copy)]
+#[enum_stringify(case = "lower")]
+pub enum ShiftOp {
+    Shl,
+    Shr,
+    Sar,
+    Rol,
+    Ror,
+    Rcl,
+    Rcr,
+}
+
+impl ShiftOp {
+    /// Shifts or rotates `value`, `bits` wide, by one bit. Returns the new value and carry.
+    fn step(self, value: u16, bits: u32, carry: bool) -> (u16, bool) {
+        let msb = 1 << (bits - 1);
+        let mask = ((1u32 << bits) - 1) as u16;
+        let (shifted_out_high, shifted_out_low) = (value & msb != 0, value & 1 != 0);
+        let value = match self {
+            Self::Shl => value << 1,
+            Self::Shr => value >> 1,
+            Self::Sar => (value >> 1) | (value & msb),
+            Self::Rol => (value << 1) | shifted_out_high as u16,
+            Self::Ror => (value >> 1) | if shifted_out_low { msb } else { 0 },
+            Self::Rcl => (value << 1) | carry as u16,
+            Self::Rcr => (value >> 1) | if carry { msb } else { 0 },
+        };
+        let carry = match self {
+            Self::Shl | Self::Rol | Self::Rcl => shifted_out_high,
+            Self::Shr | Self::Sar | Self::Ror | Self::Rcr => shifted_out_low,
+        };
+        (value & mask, carry)
+    }
+}
+
+/// Shifts and rotates by 1 or by CL. A count of zero leaves the operand and flags alone.
+pub fn handle_shift(
+    op: ShiftOp,
+    inst: &Instruction,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let count = match inst.second {
+        Some(Operand::Immediate(Data::U8(1))) => 1,
+        Some(Operand::Register(Register::CL)) => u8::try_from(&registers.get(Register::CL))
+            .map_err(|_| SimErrorKind::invalid_operands(inst))?,
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+
+    let original = match first {
+        Operand::Register(reg) => registers.get(reg),
+        Operand::EffectiveAddress(ea) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            memory
+                .load_data(addr, ea.wide())
+                .ok_or_else(|| SimErrorKind::invalid_operands(inst))?
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    if count == 0 {
+        return Ok(());
+    }
+
+    let bits = match original {
+        Data::U8(_) => 8,
+        Data::U16(_) => 16,
+    };
+    let msb = 1u16 << (bits - 1);
+    let mut value = u16::from(original);
+    let mut carry = flags.carry;
+    for _ in 0..count {
+        (value, carry) = op.step(value, bits, carry);
+    }
+    let result = match original {
+        Data::U8(_) => Data::U8(value as u8),
+        Data::U16(_) => Data::U16(value),
+    };
+
+    match first {
+        Operand::Register(reg) => registers.set_imd(reg, result),
+        Operand::EffectiveAddress(ea) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            memory.store_data(addr, result)
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    }
+
+    flags.carry = carry;
+    // only defined for single bit shifts, the 8086 leaves the last bit's result for longer ones
+    flags.overflow = match op {
+        ShiftOp::Shl | ShiftOp::Rol | ShiftOp::Rcl => (value & msb != 0) != carry,
+        ShiftOp::Shr => u16::from(original) & msb != 0,
+        ShiftOp::Sar => false,
+        ShiftOp::Ror | ShiftOp::Rcr => ((value & msb) != 0) != ((value & (msb >> 1)) != 0),
+    };
+    if matches!(op, ShiftOp::Shl | ShiftOp::Shr | ShiftOp::Sar) {
+        flags.zero = result.is_zero();
+        flags.sign = result.is_signed();
+        flags.parity = result.is_lower_byte_even_parity();
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use crate::{
+    cpu::{Memory, Registers},
+    disasm::Instruction,
+    fields::{CsIp, Data, Operand, Operation, Register, SegmentRegister},
+    simulator::SimErrorKind,
+};
+
+/// Decrements SP by two and stores `val` at SS:SP
+pub fn push(registers: &mut Registers, memory: &mut Memory, val: u16) {
+    let sp = u16::from(registers.get(Register::SP)).wrapping_sub(2);
+    registers.set_imd(Register::SP, Data::U16(sp));
+    memory.store_16(registers.stack_top(), val);
+}
+
+/// Loads the word at SS:SP and increments SP by two
+pub fn pop(registers: &mut Registers, memory: &Memory) -> u16 {
+    let val = memory.load_16(registers.stack_top());
+    release(registers, 2);
+    val
+}
+
+/// Drops `nbytes` off the top of the stack
+fn release(registers: &mut Registers, nbytes: u16) {
+    let sp = u16::from(registers.get(Register::SP)).wrapping_add(nbytes);
+    registers.set_imd(Register::SP, Data::U16(sp));
+}
+
+/// Value of a word sized register or memory operand
+pub(super) fn read_word(
+    inst: &Instruction,
+    operand: Operand,
+    registers: &Registers,
+    memory: &Memory,
+) -> Result<u16, SimErrorKind> {
+    Ok(match operand {
+        Operand::Register(reg) => registers.get(reg).into(),
+        Operand::SR(sr) => registers.get_sr(sr).into(),
+        Operand::EffectiveAddress(ea) => {
+            memory.load_16(registers.effective_address(ea, inst.segment_override()))
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    })
+}
+
+pub fn handle_push(
+    inst: &Instruction,
+    registers: &mut Registers,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let val = match first {
+        // SP is decremented before the operand is read, so `push sp` pushes the new value
+        Operand::Register(Register::SP) => u16::from(registers.get(Register::SP)).wrapping_sub(2),
+        operand => read_word(inst, operand, registers, memory)?,
+    };
+    push(registers, memory, val);
+    Ok(())
+}
+
+pub fn handle_pop(
+    inst: &Instruction,
+    registers: &mut Registers,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    match first {
+        Operand::Register(reg) => {
+            let val = pop(registers, memory);
+            registers.set_imd(reg, Data::U16(val));
+        }
+        Operand::SR(sr) => {
+            let val = pop(registers, memory);
+            registers.set_sr_imd(sr, Data::U16(val));
+        }
+        Operand::EffectiveAddress(ea) => {
+            let val = pop(registers, memory);
+            memory.store_16(
+                registers.effective_address(ea, inst.segment_override()),
+                val,
+            );
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    }
+    Ok(())
+}
+
+/// Near and far calls. `ip` already points past the call.
+pub fn handle_call(
+    inst: &Instruction,
+    ip: &mut u16,
+    registers: &mut Registers,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let first = inst
+        .first
+        .ok_or_else(|| SimErrorKind::invalid_operands(inst))?;
+    let far_target = match (inst.operation, first) {
+        (Operation::Call, Operand::Increment(inc)) => {
+            push(registers, memory, *ip);
+            *ip = ip.wrapping_add_signed(i16::from(inc));
+            None
+        }
+        (Operation::Call, Operand::CsIp(target)) => Some(target),
+        (Operation::Call, operand) => {
+            let target = read_word(inst, operand, registers, memory)?;
+            push(registers, memory, *ip);
+            *ip = target;
+            None
+        }
+        (Operation::CallFar, Operand::EffectiveAddress(ea)) => {
+            let addr = registers.effective_address(ea, inst.segment_override());
+            Some(CsIp {
+                instruction_pointer: memory.load_16(addr),
+                code_segment: memory.load_16(addr.offset_by(2)),
+            })
+        }
+        _ => return Err(SimErrorKind::invalid_operands(inst)),
+    };
+    if let Some(target) = far_target {
+        let cs = registers.cs();
+        push(registers, memory, cs);
+        push(registers, memory, *ip);
+        registers.set_sr_imd(SegmentRegister::CS, Data::U16(target.code_segment));
+        *ip = target.instruction_pointer;
+    }
+    Ok(())
+}
+
+/// Near and far returns, releasing the extra stack bytes given by `ret imm16`
+pub fn handle_ret(inst: &Instruction, ip: &mut u16, registers: &mut Registers, memory: &Memory) {
+    *ip = pop(registers, memory);
+    if inst.operation == Operation::RetFar {
+        let cs = pop(registers, memory);
+        registers.set_sr_imd(SegmentRegister::CS, Data::U16(cs));
+    }
+    if let Some(Operand::Immediate(nbytes)) = inst.first {
+        release(registers, nbytes.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Inc;
+
+    #[test]
+    fn push_then_pop_restores_value() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+        registers.set_imd(Register::SP, Data::U16(0x100));
+        push(&mut registers, &mut memory, 0xbeef);
+        assert_eq!(registers.get(Register::SP), Data::U16(0xfe));
+        assert_eq!(memory.load_16(registers.stack_top()), 0xbeef);
+        assert_eq!(pop(&mut registers, &memory), 0xbeef);
+        assert_eq!(registers.get(Register::SP), Data::U16(0x100));
+    }
+
+    #[test]
+    fn near_call_and_ret_with_release() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+        let mut ip = 0x10;
+        push(&mut registers, &mut memory, 7);
+        let call = Instruction {
+            operation: Operation::Call,
+            first: Some(Inc::I16(0x20).into()),
+            second: None,
+            prefix: None,
+            size: 3,
+        };
+        handle_call(&call, &mut ip, &mut registers, &mut memory).unwrap();
+        assert_eq!(ip, 0x30);
+        let ret = Instruction {
+            operation: Operation::Ret,
+            first: Some(Data::U16(2).into()),
+            second: None,
+            prefix: None,
+            size: 3,
+        };
+        handle_ret(&ret, &mut ip, &mut registers, &memory);
+        assert_eq!(ip, 0x10);
+        assert_eq!(registers.get(Register::SP), Data::U16(0));
+    }
+}
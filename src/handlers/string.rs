@@ -0,0 +1,112 @@
+use crate::{
+    cpu::{Flags, Memory, Registers},
+    disasm::Instruction,
+    fields::{Data, Operation, Register, Wide},
+    instruction::InstructionPrefix,
+    simulator::SimErrorKind,
+};
+
+use super::ArithmeticOp;
+
+/// Steps SI or DI past the element just processed, backwards when DF is set
+fn advance(registers: &mut Registers, reg: Register, wide: Wide, flags: &Flags) {
+    let size = if wide == Wide::Word { 2 } else { 1 };
+    let val = u16::from(registers.get(reg));
+    let val = if flags.direction {
+        val.wrapping_sub(size)
+    } else {
+        val.wrapping_add(size)
+    };
+    registers.set_imd(reg, Data::U16(val));
+}
+
+fn accumulator(wide: Wide) -> Register {
+    if wide == Wide::Word {
+        Register::AX
+    } else {
+        Register::AL
+    }
+}
+
+/// One iteration of a string instruction, from DS:SI (or the override) to ES:DI
+fn iterate(
+    inst: &Instruction,
+    wide: Wide,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) {
+    let load = |memory: &Memory, addr| memory.load_data(addr, wide).expect("sized load");
+    let src = registers.string_source(inst.segment_override());
+    let dst = registers.string_destination();
+    let (uses_si, uses_di) = match inst.operation {
+        Operation::MOVSB | Operation::MOVSW => {
+            memory.store_data(dst, load(memory, src));
+            (true, true)
+        }
+        Operation::LODSB | Operation::LODSW => {
+            registers.set_imd(accumulator(wide), load(memory, src));
+            (true, false)
+        }
+        Operation::STOSB | Operation::STOSW => {
+            memory.store_data(dst, registers.get(accumulator(wide)));
+            (false, true)
+        }
+        Operation::CMPSB | Operation::CMPSW => {
+            let (lhs, rhs) = (load(memory, src), load(memory, dst));
+            flags.set(lhs, rhs, ArithmeticOp::Cmp, lhs - rhs);
+            (true, true)
+        }
+        _ => {
+            let (lhs, rhs) = (registers.get(accumulator(wide)), load(memory, dst));
+            flags.set(lhs, rhs, ArithmeticOp::Cmp, lhs - rhs);
+            (false, true)
+        }
+    };
+    if uses_si {
+        advance(registers, Register::SI, wide, flags);
+    }
+    if uses_di {
+        advance(registers, Register::DI, wide, flags);
+    }
+}
+
+/// MOVS, LODS, STOS, CMPS and SCAS. Under a REP prefix the whole loop runs here, counting CX
+/// down to zero, with CMPS and SCAS also stopping once ZF no longer matches REPE or REPNE.
+pub fn handle_string(
+    inst: &Instruction,
+    registers: &mut Registers,
+    flags: &mut Flags,
+    memory: &mut Memory,
+) -> Result<(), SimErrorKind> {
+    let wide = match inst.operation {
+        Operation::MOVSB
+        | Operation::LODSB
+        | Operation::STOSB
+        | Operation::CMPSB
+        | Operation::SCASB => Wide::Byte,
+        Operation::MOVSW
+        | Operation::LODSW
+        | Operation::STOSW
+        | Operation::CMPSW
+        | Operation::SCASW => Wide::Word,
+        _ => return Err(SimErrorKind::unsupported(inst)),
+    };
+    let repeat_while_zero = match inst.prefix {
+        Some(InstructionPrefix::RepE | InstructionPrefix::RepESegmentOverride(_)) => true,
+        Some(InstructionPrefix::RepNE | InstructionPrefix::RepNESegmentOverride(_)) => false,
+        _ => {
+            iterate(inst, wide, registers, flags, memory);
+            return Ok(());
+        }
+    };
+
+    while registers.cx() != 0 {
+        iterate(inst, wide, registers, flags, memory);
+        registers.dec_cx();
+        if inst.operation.is_string_compare() && flags.zero != repeat_while_zero {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -2,16 +2,49 @@ use std::fmt::{self, Display};
 use std::mem::swap;
 use std::str::FromStr;
 
-use crate::fields::{Operand, Operation, SegmentRegister};
+use crate::disasm::DecodeErrorKind;
+use crate::fields::{Data, Operand, Operation, SegmentRegister};
 use crate::ByteStream;
 
 #[derive(Debug, Default, PartialEq, Copy, Clone)]
 pub enum InstructionPrefix {
     #[default]
     Lock,
-    Rep,
+    /// F3, plain `rep` on MOVS, LODS and STOS
+    RepE,
+    RepNE,
     SegmentOverride(SegmentRegister),
     LockSegmentOverride(SegmentRegister),
+    RepESegmentOverride(SegmentRegister),
+    RepNESegmentOverride(SegmentRegister),
+}
+
+impl InstructionPrefix {
+    /// Both prefixes as one, if the pair is one that an instruction can carry together
+    pub fn join(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Lock, Self::SegmentOverride(sr)) | (Self::SegmentOverride(sr), Self::Lock) => {
+                Some(Self::LockSegmentOverride(sr))
+            }
+            (Self::RepE, Self::SegmentOverride(sr)) | (Self::SegmentOverride(sr), Self::RepE) => {
+                Some(Self::RepESegmentOverride(sr))
+            }
+            (Self::RepNE, Self::SegmentOverride(sr)) | (Self::SegmentOverride(sr), Self::RepNE) => {
+                Some(Self::RepNESegmentOverride(sr))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn segment_override(self) -> Option<SegmentRegister> {
+        match self {
+            Self::SegmentOverride(sr)
+            | Self::LockSegmentOverride(sr)
+            | Self::RepESegmentOverride(sr)
+            | Self::RepNESegmentOverride(sr) => Some(sr),
+            Self::Lock | Self::RepE | Self::RepNE => None,
+        }
+    }
 }
 
 impl FromStr for InstructionPrefix {
@@ -20,7 +53,8 @@ impl FromStr for InstructionPrefix {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "Lock" => Ok(Self::Lock),
-            "Rep" => Ok(Self::Rep),
+            "RepE" => Ok(Self::RepE),
+            "RepNE" => Ok(Self::RepNE),
             "SegmentOverrideCS" => Ok(Self::SegmentOverride(SegmentRegister::CS)),
             "SegmentOverrideES" => Ok(Self::SegmentOverride(SegmentRegister::ES)),
             "SegmentOverrideDS" => Ok(Self::SegmentOverride(SegmentRegister::DS)),
@@ -41,6 +75,7 @@ pub struct Inst {
     pub second: Option<Operand>,
     pub prefix: Option<InstructionPrefix>,
     size: Option<usize>,
+    bytes: Vec<u8>,
 }
 
 impl Inst {
@@ -51,6 +86,7 @@ impl Inst {
             second: None,
             prefix: None,
             size: None,
+            bytes: Vec::new(),
         }
     }
 
@@ -61,6 +97,7 @@ impl Inst {
             second: None,
             prefix: None,
             size: None,
+            bytes: Vec::new(),
         }
     }
 
@@ -71,6 +108,7 @@ impl Inst {
             second: None,
             prefix: None,
             size: None,
+            bytes: Vec::new(),
         }
     }
 
@@ -81,6 +119,7 @@ impl Inst {
             second: Some(second),
             prefix: None,
             size: None,
+            bytes: Vec::new(),
         }
     }
 
@@ -95,9 +134,17 @@ impl Inst {
             second: Some(second.into()),
             prefix: None,
             size: None,
+            bytes: Vec::new(),
         }
     }
 
+    /// Data pseudo-instruction standing in for a byte that couldn't be decoded
+    pub fn data_byte(byte: u8) -> Self {
+        let mut inst = Inst::with_operand(Operation::DB, Data::U8(byte).into());
+        inst.set_bytes(&[byte]);
+        inst
+    }
+
     pub fn add_instruction_prefix(&mut self, prefix: InstructionPrefix) {
         self.prefix = Some(prefix);
     }
@@ -109,6 +156,16 @@ impl Inst {
     pub fn size(&self) -> Option<usize> {
         self.size
     }
+
+    /// Records the machine code the instruction was decoded from, along with its size
+    pub fn set_bytes(&mut self, bytes: &[u8]) {
+        self.bytes = bytes.to_vec();
+        self.set_size(bytes.len());
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
 }
 
 impl Display for Inst {
@@ -116,14 +173,37 @@ impl Display for Inst {
         // Handle instruction prefix
         if let Some(prefix) = self.prefix {
             match prefix {
-                InstructionPrefix::Rep => write!(f, "rep ")?,
-                InstructionPrefix::Lock => write!(f, "lock ")?,
-                InstructionPrefix::LockSegmentOverride(_) => write!(f, "lock ")?,
-                _ => (),
+                InstructionPrefix::RepE | InstructionPrefix::RepESegmentOverride(_)
+                    if self.operation.is_string_compare() =>
+                {
+                    write!(f, "repe ")?
+                }
+                InstructionPrefix::RepE | InstructionPrefix::RepESegmentOverride(_) => {
+                    write!(f, "rep ")?
+                }
+                InstructionPrefix::RepNE | InstructionPrefix::RepNESegmentOverride(_) => {
+                    write!(f, "repne ")?
+                }
+                InstructionPrefix::Lock | InstructionPrefix::LockSegmentOverride(_) => {
+                    write!(f, "lock ")?
+                }
+                InstructionPrefix::SegmentOverride(_) => (),
             }
         }
+        let segment = self.prefix.and_then(InstructionPrefix::segment_override);
+        // without a memory operand to carry it, the override is written as a prefix, `es movsb`
+        let has_ea = [self.first, self.second]
+            .iter()
+            .any(|operand| matches!(operand, Some(Operand::EffectiveAddress(_))));
+        if let (Some(sr), false) = (segment, has_ea) {
+            write!(f, "{} ", sr)?;
+        }
 
         // special cases / workarounds / hacks
+        // Undecodable bytes are emitted in hex so that they stand out in the listing
+        if let (Operation::DB, Some(Operand::Immediate(data))) = (self.operation, self.first) {
+            return write!(f, "{} {:#04x}", self.operation, u16::from(data));
+        }
         // Push & Pop implicitly assume 16-bit operations in 8086. But NASM is complaining, so hardcoding size
         if [Operation::Push, Operation::Pop].contains(&self.operation) && self.second.is_none() {
             if let Some(Operand::EffectiveAddress(x)) = self.first {
@@ -141,12 +221,9 @@ impl Display for Inst {
         let handle_ea = |x: Operand| -> String {
             if let Operand::EffectiveAddress(ea) = x {
                 let wide = ea.wide();
-                match self.prefix {
-                    Some(InstructionPrefix::SegmentOverride(x))
-                    | Some(InstructionPrefix::LockSegmentOverride(x)) => {
-                        format!("{}{}:{}", wide, x, ea)
-                    }
-                    _ => format!("{}{}", wide, ea),
+                match segment {
+                    Some(x) => format!("{}{}:{}", wide, x, ea),
+                    None => format!("{}{}", wide, ea),
                 }
             } else {
                 x.to_string()
@@ -154,23 +231,51 @@ impl Display for Inst {
         };
 
         write!(f, "{}", self.operation)?;
-        if first.is_some() {
-            write!(f, " {}", handle_ea(first.unwrap()))?;
-            if second.is_some() {
-                write!(f, ", {}", handle_ea(second.unwrap()))?;
+        if let Some(first) = first {
+            write!(f, " {}", handle_ea(first))?;
+            if let Some(second) = second {
+                write!(f, ", {}", handle_ea(second))?;
             }
         }
         Ok(())
     }
 }
 
-pub trait InstructionDecoder {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst;
+pub trait InstructionDecoder: Sync + Send {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind>;
 }
 
 impl InstructionDecoder for InstructionPrefix {
-    fn decode(&self, _first_byte: u8, _byte_stream: &mut ByteStream, _op: Operation) -> Inst {
+    fn decode(
+        &self,
+        _first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        _op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         // this is a dirty hack to let the macro in decoder.rs compile
         unreachable!()
     }
 }
+
+pub trait InstructionEncoder {
+    /// Encodes the operands of `inst` into bytes matching `opcode` under `mask`, or returns
+    /// `None` if this operand shape can't express them
+    fn encode(&self, opcode: &[u8], mask: &[u8], inst: &Inst) -> Option<Vec<u8>>;
+}
+
+impl InstructionEncoder for InstructionPrefix {
+    fn encode(&self, _opcode: &[u8], _mask: &[u8], _inst: &Inst) -> Option<Vec<u8>> {
+        // same dirty hack as the decoder above
+        unreachable!()
+    }
+}
+
+/// Operand shape that can be both decoded and encoded
+pub trait InstructionCodec: InstructionDecoder + InstructionEncoder {}
+
+impl<T: InstructionDecoder + InstructionEncoder> InstructionCodec for T {}
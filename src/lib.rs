@@ -1,3 +1,4 @@
+mod asm;
 mod cpu;
 mod disasm;
 mod fields;
@@ -8,7 +9,15 @@ pub mod simulator;
 
 use std::iter::Peekable;
 
-pub use disasm::{decode_8086, write_8086};
+pub use asm::{assemble_8086, AsmError, AsmErrorKind};
+pub use cpu::{
+    Address, FlagEffect, FlagEffects, Flags, Memory, MemoryWrite, RegisterChange, Registers,
+    UndefinedFlags,
+};
+pub use disasm::{
+    decode_8086, decode_8086_lenient, encode, try_decode_8086, write_8086, write_8086_listing,
+    DecodeError, DecodeErrorKind, DecodedInst, Decoder, EncodeError, Instruction, Program,
+};
 
 pub struct EnumeratePeekable<I: Iterator> {
     iter: Peekable<I>,
@@ -27,13 +36,6 @@ impl<I: Iterator> EnumeratePeekable<I> {
         self.iter.peek()
     }
 
-    fn next_with_index(&mut self) -> Option<(usize, I::Item)> {
-        self.iter.next().map(|i| {
-            self.count += 1;
-            (self.count - 1, i)
-        })
-    }
-
     fn vended_count(&self) -> usize {
         self.count
     }
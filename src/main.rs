@@ -1,10 +1,10 @@
-use sim8086::{decode_8086, write_8086};
-use std::{env, fs::File, io::Read};
+use sim8086::{decode_8086_lenient, try_decode_8086, write_8086, write_8086_listing};
+use std::{env, fs::File, io::Read, process};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        println!("Usage: {} <file_path>", args[0]);
+        println!("Usage: {} <file_path> [--lenient] [--listing]", args[0]);
         return;
     }
 
@@ -14,10 +14,27 @@ fn main() {
     let mut bytes = Vec::new();
     file.read_to_end(&mut bytes).expect("read file");
 
-    let instructions = decode_8086(&bytes[..]);
+    let lenient = args[2..].iter().any(|arg| arg == "--lenient");
+    let listing = args[2..].iter().any(|arg| arg == "--listing");
+    let instructions = if lenient {
+        decode_8086_lenient(&bytes[..])
+    } else {
+        match try_decode_8086(&bytes[..]) {
+            Ok(instructions) => instructions,
+            Err(err) => {
+                eprintln!("{}: {}", file_path, err);
+                process::exit(1);
+            }
+        }
+    };
 
     let out_filepath = format!("{}.8086.decoded", file_path);
     let mut out_file = File::create(out_filepath).expect("Open output file");
 
-    write_8086(&instructions, &mut out_file).expect("Failed to write to output file");
+    if listing {
+        write_8086_listing(&instructions, &mut out_file)
+    } else {
+        write_8086(&instructions, &mut out_file)
+    }
+    .expect("Failed to write to output file");
 }
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithData, WithWideField},
-    fields::{Data, EffectiveAddress, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData, WithWideField},
+    fields::{Data, EffectiveAddress, Operand, Operation, Register, Wide},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -15,10 +15,15 @@ impl WithWideField for AccDA {
 impl WithData for AccDA {}
 
 impl InstructionDecoder for AccDA {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let wide = Self::is_wide(first_byte);
         let ws = Self::get_wide_size(first_byte);
-        let data = Self::extract_data(first_byte, byte_stream);
+        let data = Self::extract_data(first_byte, byte_stream)?;
         let acc = if wide { Register::AX } else { Register::AL }.into();
         let direct_address = EffectiveAddress::DirectAddress(
             match data {
@@ -28,7 +33,27 @@ impl InstructionDecoder for AccDA {
             ws,
         )
         .into();
-        Inst::with_operands(op, acc, direct_address)
+        Ok(Inst::with_operands(op, acc, direct_address))
+    }
+}
+
+impl InstructionEncoder for AccDA {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(acc)), Some(Operand::EffectiveAddress(ea))) =
+            (inst.first, inst.second)
+        else {
+            return None;
+        };
+        // the byte form is decoded with a one byte address, leave it to the ModRM encoding
+        let EffectiveAddress::DirectAddress(addr, Wide::Word) = ea else {
+            return None;
+        };
+        if acc != Register::AX {
+            return None;
+        }
+        let mut bytes = vec![opcode[0] | Self::emit_wide(true)];
+        bytes.extend(Self::emit_data(Data::U16(addr), true)?);
+        Some(bytes)
     }
 }
 
@@ -44,11 +69,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 2] = [0b10100000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::AL),
@@ -61,11 +88,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 3] = [0b10100001, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::AX),
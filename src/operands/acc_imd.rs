@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithData, WithWideField},
-    fields::{Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData, WithWideField},
+    fields::{Operand, Operation, Register},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -15,11 +15,34 @@ impl WithWideField for AccImd {
 impl WithData for AccImd {}
 
 impl InstructionDecoder for AccImd {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let wide = Self::is_wide(first_byte);
-        let data = Self::extract_data(first_byte, byte_stream).into();
+        let data = Self::extract_data(first_byte, byte_stream)?.into();
         let acc = if wide { Register::AX } else { Register::AL }.into();
-        Inst::with_operands(op, acc, data)
+        Ok(Inst::with_operands(op, acc, data))
+    }
+}
+
+impl InstructionEncoder for AccImd {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(acc)), Some(Operand::Immediate(data))) =
+            (inst.first, inst.second)
+        else {
+            return None;
+        };
+        let wide = match acc {
+            Register::AX => true,
+            Register::AL => false,
+            _ => return None,
+        };
+        let mut bytes = vec![opcode[0] | Self::emit_wide(wide)];
+        bytes.extend(Self::emit_data(data, wide)?);
+        Some(bytes)
     }
 }
 
@@ -35,11 +58,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 2] = [0b00111100, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Cmp
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Cmp
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Cmp,
                 Operand::Register(Register::AL),
@@ -52,11 +77,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 3] = [0b00111101, 0b00000001, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Cmp
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Cmp
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Cmp,
                 Operand::Register(Register::AX),
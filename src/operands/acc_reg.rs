@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithRegField, WithWideField},
+    disasm::{DecodeErrorKind, WithRegField, WithWideField},
     fields::{Operand, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -18,9 +18,29 @@ impl WithWideField for AccReg {
 }
 
 impl InstructionDecoder for AccReg {
-    fn decode(&self, first_byte: u8, _byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let reg = Self::extract_reg(first_byte, first_byte).into();
-        Inst::with_operands(op, Operand::Register(Register::AX), reg)
+        Ok(Inst::with_operands(
+            op,
+            Operand::Register(Register::AX),
+            reg,
+        ))
+    }
+}
+
+impl InstructionEncoder for AccReg {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(Register::AX)), Some(Operand::Register(reg))) =
+            (inst.first, inst.second)
+        else {
+            return None;
+        };
+        Some(vec![opcode[0] | Self::emit_reg(reg, true)?])
     }
 }
 
@@ -35,11 +55,13 @@ mod tests {
     fn reg() {
         let bytes: [u8; 1] = [0b10010110];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::XCHG
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::XCHG
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::XCHG,
                 Operand::Register(Register::AX),
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithCsIp,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithCsIp},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -11,9 +11,25 @@ pub struct CsIp;
 impl WithCsIp for CsIp {}
 
 impl InstructionDecoder for CsIp {
-    fn decode(&self, _first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let cs_ip = Self::extract_cs_ip(byte_stream).into();
-        Inst::with_operand(op, cs_ip)
+    fn decode(
+        &self,
+        _first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let cs_ip = Self::extract_cs_ip(byte_stream)?.into();
+        Ok(Inst::with_operand(op, cs_ip))
+    }
+}
+
+impl InstructionEncoder for CsIp {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::CsIp(cs_ip)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let mut bytes = vec![opcode[0]];
+        bytes.extend(Self::emit_cs_ip(cs_ip));
+        Some(bytes)
     }
 }
 
@@ -29,11 +45,13 @@ mod tests {
     fn call() {
         let bytes: [u8; 5] = [0b10011010, 0b11001000, 0b00000001, 0b01111011, 0b00000000];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Call
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Call
+                )
+                .unwrap(),
             Inst::with_operand(
                 Operation::Call,
                 Operand::CsIp(CsIpField {
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithData, WithWideField},
-    fields::{Data, EffectiveAddress, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData, WithWideField},
+    fields::{Data, EffectiveAddress, Operand, Operation, Register, Wide},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -15,10 +15,15 @@ impl WithWideField for DAAcc {
 impl WithData for DAAcc {}
 
 impl InstructionDecoder for DAAcc {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let wide = Self::is_wide(first_byte);
         let ws = Self::get_wide_size(first_byte);
-        let data = Self::extract_data(first_byte, byte_stream);
+        let data = Self::extract_data(first_byte, byte_stream)?;
         let acc = if wide { Register::AX } else { Register::AL }.into();
         let direct_address = EffectiveAddress::DirectAddress(
             match data {
@@ -28,7 +33,27 @@ impl InstructionDecoder for DAAcc {
             ws,
         )
         .into();
-        Inst::with_operands(op, direct_address, acc)
+        Ok(Inst::with_operands(op, direct_address, acc))
+    }
+}
+
+impl InstructionEncoder for DAAcc {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::EffectiveAddress(ea)), Some(Operand::Register(acc))) =
+            (inst.first, inst.second)
+        else {
+            return None;
+        };
+        // the byte form is decoded with a one byte address, leave it to the ModRM encoding
+        let EffectiveAddress::DirectAddress(addr, Wide::Word) = ea else {
+            return None;
+        };
+        if acc != Register::AX {
+            return None;
+        }
+        let mut bytes = vec![opcode[0] | Self::emit_wide(true)];
+        bytes.extend(Self::emit_data(Data::U16(addr), true)?);
+        Some(bytes)
     }
 }
 
@@ -44,11 +69,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 2] = [0b10100010, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::DirectAddress(1, Wide::Byte)),
@@ -61,11 +88,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 3] = [0b10100011, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::DirectAddress(256, Wide::Word)),
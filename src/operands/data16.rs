@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithData16,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData16},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -11,9 +11,25 @@ pub struct Data16;
 impl WithData16 for Data16 {}
 
 impl InstructionDecoder for Data16 {
-    fn decode(&self, _first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let data16 = Self::extract_data16(byte_stream).into();
-        Inst::with_operand(op, data16)
+    fn decode(
+        &self,
+        _first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let data16 = Self::extract_data16(byte_stream)?.into();
+        Ok(Inst::with_operand(op, data16))
+    }
+}
+
+impl InstructionEncoder for Data16 {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Immediate(data)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let mut bytes = vec![opcode[0]];
+        bytes.extend(Self::emit_data16(data)?);
+        Some(bytes)
     }
 }
 
@@ -29,11 +45,13 @@ mod tests {
     fn ret() {
         let bytes: [u8; 3] = [0b11000010, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Ret
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Ret
+                )
+                .unwrap(),
             Inst::with_operand(Operation::Ret, Operand::Immediate(Data::U16(256)))
         );
     }
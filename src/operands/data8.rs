@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithData8,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData8},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -11,9 +11,25 @@ pub struct Data8;
 impl WithData8 for Data8 {}
 
 impl InstructionDecoder for Data8 {
-    fn decode(&self, _first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let data8 = Self::extract_data8(byte_stream).into();
-        Inst::with_operand(op, data8)
+    fn decode(
+        &self,
+        _first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let data8 = Self::extract_data8(byte_stream)?.into();
+        Ok(Inst::with_operand(op, data8))
+    }
+}
+
+impl InstructionEncoder for Data8 {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Immediate(data)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let mut bytes = vec![opcode[0]];
+        bytes.extend(Self::emit_data8(data)?);
+        Some(bytes)
     }
 }
 
@@ -29,11 +45,13 @@ mod tests {
     fn int() {
         let bytes: [u8; 2] = [0b11001101, 0b0000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::INT
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::INT
+                )
+                .unwrap(),
             Inst::with_operand(Operation::INT, Operand::Immediate(Data::U8(1)))
         );
     }
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithWideField,
-    fields::{Data, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, DecodeErrorKind, WithWideField},
+    fields::{Data, Operand, Operation, Register},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -13,15 +13,40 @@ impl WithWideField for FixedPort {
 }
 
 impl InstructionDecoder for FixedPort {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let wide = Self::is_wide(first_byte);
         let acc = if wide { Register::AX } else { Register::AL }.into();
-        let data8 = Data::U8(byte_stream.next().expect("extract second byte").to_owned()).into();
-        Inst::with_operands(
+        let data8 = Data::U8(next_byte(byte_stream)?).into();
+        Ok(Inst::with_operands(
             op,
             if op == Operation::IN { acc } else { data8 },
             if op == Operation::IN { data8 } else { acc },
-        )
+        ))
+    }
+}
+
+impl InstructionEncoder for FixedPort {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (acc, port) = if inst.operation == Operation::IN {
+            (inst.first, inst.second)
+        } else {
+            (inst.second, inst.first)
+        };
+        let (Some(Operand::Register(acc)), Some(Operand::Immediate(Data::U8(port)))) = (acc, port)
+        else {
+            return None;
+        };
+        let wide = match acc {
+            Register::AX => true,
+            Register::AL => false,
+            _ => return None,
+        };
+        Some(vec![opcode[0] | Self::emit_wide(wide), port])
     }
 }
 
@@ -37,11 +62,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 2] = [0b11100101, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::IN
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::IN
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::IN,
                 Operand::Register(Register::AX),
@@ -54,11 +81,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 2] = [0b11100110, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::OUT
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::OUT
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::OUT,
                 Operand::Immediate(Data::U8(1)),
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithInc16,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithInc16},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -11,9 +11,25 @@ pub struct Inc16;
 impl WithInc16 for Inc16 {}
 
 impl InstructionDecoder for Inc16 {
-    fn decode(&self, _first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let inc16 = Self::extract_inc16(byte_stream).into();
-        Inst::with_operand(op, inc16)
+    fn decode(
+        &self,
+        _first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let inc16 = Self::extract_inc16(byte_stream)?.into();
+        Ok(Inst::with_operand(op, inc16))
+    }
+}
+
+impl InstructionEncoder for Inc16 {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Increment(inc)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let mut bytes = vec![opcode[0]];
+        bytes.extend(Self::emit_inc16(inc)?);
+        Some(bytes)
     }
 }
 
@@ -29,11 +45,13 @@ mod tests {
     fn call() {
         let bytes: [u8; 3] = [0b11101000, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Call
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Call
+                )
+                .unwrap(),
             Inst::with_operand(Operation::Call, Operand::Increment(Inc::I16(256)))
         );
     }
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithInc8,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithInc8},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -11,9 +11,25 @@ pub struct Inc8;
 impl WithInc8 for Inc8 {}
 
 impl InstructionDecoder for Inc8 {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let inc8 = Self::extract_inc8(first_byte, byte_stream).into();
-        Inst::with_operand(op, inc8)
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let inc8 = Self::extract_inc8(first_byte, byte_stream)?.into();
+        Ok(Inst::with_operand(op, inc8))
+    }
+}
+
+impl InstructionEncoder for Inc8 {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Increment(inc)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let mut bytes = vec![opcode[0]];
+        bytes.extend(Self::emit_inc8(inc)?);
+        Some(bytes)
     }
 }
 
@@ -29,11 +45,13 @@ mod tests {
     fn je() {
         let bytes: [u8; 2] = [0b01110100, 0b11111110];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::JE
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::JE
+                )
+                .unwrap(),
             Inst::with_operand(Operation::JE, Operand::Increment(Inc::I8(-2)))
         );
     }
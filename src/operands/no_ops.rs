@@ -1,6 +1,7 @@
 use crate::{
+    disasm::DecodeErrorKind,
     fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -8,8 +9,19 @@ use crate::{
 pub struct NoOps;
 
 impl InstructionDecoder for NoOps {
-    fn decode(&self, _first_byte: u8, _byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        Inst::new(op)
+    fn decode(
+        &self,
+        _first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        Ok(Inst::new(op))
+    }
+}
+
+impl InstructionEncoder for NoOps {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        (inst.first.is_none() && inst.second.is_none()).then(|| opcode.to_vec())
     }
 }
 
@@ -23,11 +35,13 @@ mod tests {
     fn xlat() {
         let bytes: [u8; 1] = [0b11010111];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::XLAT
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::XLAT
+                )
+                .unwrap(),
             Inst::new(Operation::XLAT)
         );
     }
@@ -1,6 +1,7 @@
 use crate::{
+    disasm::DecodeErrorKind,
     fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -8,9 +9,20 @@ use crate::{
 pub struct NoOps2;
 
 impl InstructionDecoder for NoOps2 {
-    fn decode(&self, _first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        _first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         byte_stream.next();
-        Inst::new(op)
+        Ok(Inst::new(op))
+    }
+}
+
+impl InstructionEncoder for NoOps2 {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        (inst.first.is_none() && inst.second.is_none()).then(|| opcode.to_vec())
     }
 }
 
@@ -25,7 +37,9 @@ mod tests {
         let bytes: [u8; 2] = [0b11010100, 0b00001010];
         let mut stream = ByteStream::new(bytes[1..].iter());
         assert_eq!(
-            DECODER.decode(bytes[0], &mut stream, Operation::AAM),
+            DECODER
+                .decode(bytes[0], &mut stream, Operation::AAM)
+                .unwrap(),
             Inst::new(Operation::AAM)
         );
         assert!(stream.next().is_none())
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithRegField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithRegField, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -18,9 +18,23 @@ impl WithWideField for Reg {
 }
 
 impl InstructionDecoder for Reg {
-    fn decode(&self, first_byte: u8, _byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let reg = Self::extract_reg(first_byte, first_byte).into();
-        Inst::with_operand(op, reg)
+        Ok(Inst::with_operand(op, reg))
+    }
+}
+
+impl InstructionEncoder for Reg {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(reg)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        Some(vec![opcode[0] | Self::emit_reg(reg, true)?])
     }
 }
 
@@ -35,11 +49,13 @@ mod tests {
     fn reg() {
         let bytes: [u8; 1] = [0b01010011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Push
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Push
+                )
+                .unwrap(),
             Inst::with_operand(Operation::Push, Operand::Register(Register::BX))
         )
     }
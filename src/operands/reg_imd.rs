@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithData, WithRegField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithData, WithRegField, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -19,10 +19,29 @@ impl WithRegField for RegImd {
 impl WithData for RegImd {}
 
 impl InstructionDecoder for RegImd {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let reg = Self::extract_reg(first_byte, first_byte).into();
-        let data = Self::extract_data(first_byte, byte_stream).into();
-        Inst::with_operands(op, reg, data)
+        let data = Self::extract_data(first_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(op, reg, data))
+    }
+}
+
+impl InstructionEncoder for RegImd {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(reg)), Some(Operand::Immediate(data))) =
+            (inst.first, inst.second)
+        else {
+            return None;
+        };
+        let wide = reg.is_wide();
+        let mut bytes = vec![opcode[0] | Self::emit_wide(wide) | Self::emit_reg(reg, wide)?];
+        bytes.extend(Self::emit_data(data, wide)?);
+        Some(bytes)
     }
 }
 
@@ -38,11 +57,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 2] = [0b10110000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::AL),
@@ -55,11 +76,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 3] = [0b10111000, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::AX),
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithDestField, WithRMField, WithRegField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, DecodeErrorKind, WithDestField, WithRMField, WithRegField, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -23,20 +23,47 @@ impl WithWideField for RegRM {
 }
 
 impl InstructionDecoder for RegRM {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
 
         let reg = Self::extract_reg(first_byte, second_byte).into();
         let is_reg_dest = Self::is_dest_in_reg_field(first_byte);
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        Inst::with_operands(
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(
             op,
             if is_reg_dest { reg } else { rm },
             if is_reg_dest { rm } else { reg },
-        )
+        ))
+    }
+}
+
+impl InstructionEncoder for RegRM {
+    fn encode(&self, opcode: &[u8], mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(first), Some(second)) = (inst.first, inst.second) else {
+            return None;
+        };
+        // prefer the form with the register in the source, unless the opcode pins the d bit
+        [(false, second, first), (true, first, second)]
+            .into_iter()
+            .filter(|&(is_reg_dest, _, _)| Self::allows_dest(opcode[0], mask[0], is_reg_dest))
+            .find_map(|(is_reg_dest, reg, rm)| {
+                let Operand::Register(reg) = reg else {
+                    return None;
+                };
+                let wide = reg.is_wide();
+                let (modrm, disp) = Self::emit_rm(rm, wide)?;
+                let mut bytes = vec![
+                    opcode[0] | Self::emit_dest(is_reg_dest) | Self::emit_wide(wide),
+                    Self::emit_reg(reg, wide)? | modrm,
+                ];
+                bytes.extend(disp);
+                Some(bytes)
+            })
     }
 }
 
@@ -51,11 +78,13 @@ mod tests {
     fn reg_to_reg_wide() {
         let bytes: [u8; 2] = [0b10001001, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BX),
@@ -68,11 +97,13 @@ mod tests {
     fn reg_to_reg_not_wide() {
         let bytes: [u8; 2] = [0b10001000, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BL),
@@ -85,11 +116,13 @@ mod tests {
     fn reg_to_mem_no_disp() {
         let bytes: [u8; 2] = [0b10001000, 0b00010011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::BP_DI(None, Wide::Byte)),
@@ -102,11 +135,13 @@ mod tests {
     fn reg_to_mem_direct_address() {
         let bytes: [u8; 4] = [0b10001001, 0b00010110, 0b00000001, 0b00000000];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::DirectAddress(1, Wide::Word)),
@@ -119,11 +154,13 @@ mod tests {
     fn reg_to_mem_8bit_disp() {
         let bytes: [u8; 3] = [0b10001000, 0b01001110, 0b00000010];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::BP(2, Wide::Byte)),
@@ -136,11 +173,13 @@ mod tests {
     fn reg_to_mem_16bit_disp() {
         let bytes: [u8; 4] = [0b10001001, 0b10011000, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::BX_SI(Some(256), Wide::Word)),
@@ -153,11 +192,13 @@ mod tests {
     fn mem_16bit_disp_to_reg() {
         let bytes: [u8; 4] = [0b10001011, 0b10011000, 0b00000000, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BX),
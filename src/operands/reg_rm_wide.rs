@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithRMField, WithRegField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, DecodeErrorKind, WithRMField, WithRegField, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -20,15 +20,29 @@ impl WithWideField for RegRMW {
 }
 
 impl InstructionDecoder for RegRMW {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
 
         let reg = Self::extract_reg(first_byte, second_byte).into();
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        Inst::with_operands(op, reg, rm)
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(op, reg, rm))
+    }
+}
+
+impl InstructionEncoder for RegRMW {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::Register(reg)), Some(rm)) = (inst.first, inst.second) else {
+            return None;
+        };
+        let (modrm, disp) = Self::emit_rm(rm, true)?;
+        let mut bytes = vec![opcode[0], Self::emit_reg(reg, true)? | modrm];
+        bytes.extend(disp);
+        Some(bytes)
     }
 }
 
@@ -43,11 +57,13 @@ mod tests {
     fn lea() {
         let bytes: [u8; 2] = [0b10001101, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::LEA
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::LEA
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::LEA,
                 Operand::Register(Register::AX),
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithData, WithRMField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, operand_is_wide, DecodeErrorKind, WithData, WithRMField, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -17,14 +17,33 @@ impl WithWideField for RMImd {
 }
 
 impl InstructionDecoder for RMImd {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        let data = Self::extract_data(first_byte, byte_stream).into();
-        Inst::with_operands(op, rm, data)
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        let data = Self::extract_data(first_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(op, rm, data))
+    }
+}
+
+impl InstructionEncoder for RMImd {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(rm), Some(Operand::Immediate(data))) = (inst.first, inst.second) else {
+            return None;
+        };
+        let wide = operand_is_wide(rm)?;
+        let (modrm, disp) = Self::emit_rm(rm, wide)?;
+        let mut bytes = vec![
+            opcode[0] | Self::emit_wide(wide),
+            opcode.get(1).copied().unwrap_or(0) | modrm,
+        ];
+        bytes.extend(disp);
+        bytes.extend(Self::emit_data(data, wide)?);
+        Some(bytes)
     }
 }
 
@@ -39,11 +58,13 @@ mod tests {
     fn immediate_to_register_wide() {
         let bytes: [u8; 4] = [0b11000111, 0b11000011, 0b00000100, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BX),
@@ -56,11 +77,13 @@ mod tests {
     fn immediate_to_register_not_wide() {
         let bytes: [u8; 3] = [0b11000110, 0b11000011, 0b00000100];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BL),
@@ -73,11 +96,13 @@ mod tests {
     fn immediate_to_mem_no_disp() {
         let bytes: [u8; 3] = [0b11000110, 0b00000011, 0b00000101];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::BP_DI(None, Wide::Byte)),
@@ -92,11 +117,13 @@ mod tests {
             0b11000111, 0b00000110, 0b00000100, 0b00000000, 0b00000000, 0b00000001,
         ];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::DirectAddress(4, Wide::Word)),
@@ -109,11 +136,13 @@ mod tests {
     fn immediate_to_mem_8bit_disp() {
         let bytes: [u8; 4] = [0b11000110, 0b01000110, 0b00000100, 0b00000000];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::BP(4, Wide::Byte)),
@@ -128,11 +157,13 @@ mod tests {
             0b11000111, 0b10000100, 0b00000100, 0b00000000, 0b00000000, 0b00000001,
         ];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::EffectiveAddress(EffectiveAddress::SI(Some(4), Wide::Word)),
@@ -1,7 +1,10 @@
 use crate::{
-    disasm::{WithDataS, WithRMField, WithSignField, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{
+        next_byte, operand_is_wide, DecodeErrorKind, WithDataS, WithRMField, WithSignField,
+        WithWideField,
+    },
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -19,14 +22,31 @@ impl WithWideField for RMImdS {
 }
 
 impl InstructionDecoder for RMImdS {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        let data = Self::extract_data(first_byte, byte_stream).into();
-        Inst::with_operands(op, rm, data)
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        let data = Self::extract_data(first_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(op, rm, data))
+    }
+}
+
+impl InstructionEncoder for RMImdS {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(rm), Some(Operand::Immediate(data))) = (inst.first, inst.second) else {
+            return None;
+        };
+        let wide = operand_is_wide(rm)?;
+        let (modrm, disp) = Self::emit_rm(rm, wide)?;
+        let (sign, data) = Self::emit_data(data, wide)?;
+        let mut bytes = vec![opcode[0] | sign | Self::emit_wide(wide), opcode[1] | modrm];
+        bytes.extend(disp);
+        bytes.extend(data);
+        Some(bytes)
     }
 }
 
@@ -41,11 +61,13 @@ mod tests {
     fn immediate_to_register_sign() {
         let bytes: [u8; 3] = [0b10000010, 0b11000011, 0b00000100];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Add
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Add
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Add,
                 Operand::Register(Register::BL),
@@ -58,11 +80,13 @@ mod tests {
     fn immediate_to_register_sign_not_set() {
         let bytes: [u8; 4] = [0b10000001, 0b11000011, 0b00000100, 0b00000001];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Add
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Add
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Add,
                 Operand::Register(Register::BX),
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithRMField, WithVField, WithWideField},
-    fields::{Data, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, operand_is_wide, DecodeErrorKind, WithRMField, WithVField, WithWideField},
+    fields::{Data, Operand, Operation, Register},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -20,15 +20,17 @@ impl WithVField for RMVW {
 }
 
 impl InstructionDecoder for RMVW {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
 
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
         let v = Self::is_v_set(first_byte);
-        Inst::with_operands(
+        Ok(Inst::with_operands(
             op,
             rm,
             if v {
@@ -36,7 +38,28 @@ impl InstructionDecoder for RMVW {
             } else {
                 Data::U8(1).into()
             },
-        )
+        ))
+    }
+}
+
+impl InstructionEncoder for RMVW {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(rm), Some(count)) = (inst.first, inst.second) else {
+            return None;
+        };
+        let v = match count {
+            Operand::Register(Register::CL) => true,
+            Operand::Immediate(Data::U8(1)) => false,
+            _ => return None,
+        };
+        let wide = operand_is_wide(rm)?;
+        let (modrm, disp) = Self::emit_rm(rm, wide)?;
+        let mut bytes = vec![
+            opcode[0] | Self::emit_v(v) | Self::emit_wide(wide),
+            opcode[1] | modrm,
+        ];
+        bytes.extend(disp);
+        Some(bytes)
     }
 }
 
@@ -51,11 +74,13 @@ mod tests {
     fn one() {
         let bytes: [u8; 2] = [0b11010001, 0b11100011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::SHL
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::SHL
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::SHL,
                 Operand::Register(Register::BX),
@@ -68,11 +93,13 @@ mod tests {
     fn cl() {
         let bytes: [u8; 2] = [0b11010011, 0b11100011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::SHL
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::SHL
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::SHL,
                 Operand::Register(Register::BX),
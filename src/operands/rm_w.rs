@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithRMField, WithWideField},
+    disasm::{next_byte, operand_is_wide, DecodeErrorKind, WithRMField, WithWideField},
     fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -16,14 +16,29 @@ impl WithWideField for RMW {
 }
 
 impl InstructionDecoder for RMW {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
-
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        Inst::with_operand(op, rm)
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
+
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        Ok(Inst::with_operand(op, rm))
+    }
+}
+
+impl InstructionEncoder for RMW {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(rm), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        let wide = operand_is_wide(rm)?;
+        let (modrm, disp) = Self::emit_rm(rm, wide)?;
+        let mut bytes = vec![opcode[0] | Self::emit_wide(wide), opcode[1] | modrm];
+        bytes.extend(disp);
+        Some(bytes)
     }
 }
 
@@ -38,11 +53,13 @@ mod tests {
     fn inc() {
         let bytes: [u8; 2] = [0b11111111, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::INC
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::INC
+                )
+                .unwrap(),
             Inst::with_operand(Operation::INC, Operand::Register(Register::BX))
         )
     }
@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithSR,
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{DecodeErrorKind, WithSR},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -13,9 +13,23 @@ impl WithSR for SR {
 }
 
 impl InstructionDecoder for SR {
-    fn decode(&self, first_byte: u8, _byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let sr = Self::extract_sr(first_byte).into();
-        Inst::with_operand(op, sr)
+        Ok(Inst::with_operand(op, sr))
+    }
+}
+
+impl InstructionEncoder for SR {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(Operand::SR(sr)), None) = (inst.first, inst.second) else {
+            return None;
+        };
+        Some(vec![opcode[0] | Self::emit_sr(sr)])
     }
 }
 
@@ -30,11 +44,13 @@ mod tests {
     fn sr() {
         let bytes: [u8; 1] = [0b00011110];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Push
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Push
+                )
+                .unwrap(),
             Inst::with_operand(Operation::Push, Operand::SR(SegmentRegister::DS))
         )
     }
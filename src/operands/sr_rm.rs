@@ -1,7 +1,7 @@
 use crate::{
-    disasm::{WithDestField, WithRMField, WithSR, WithWideField},
-    fields::Operation,
-    instruction::{Inst, InstructionDecoder},
+    disasm::{next_byte, DecodeErrorKind, WithDestField, WithRMField, WithSR, WithWideField},
+    fields::{Operand, Operation},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -25,20 +25,45 @@ impl WithDestField for SRRM {
 }
 
 impl InstructionDecoder for SRRM {
-    fn decode(&self, first_byte: u8, byte_stream: &mut ByteStream, op: Operation) -> Inst {
-        let second_byte = byte_stream
-            .next()
-            .expect("extract second instruction byte")
-            .to_owned();
+    fn decode(
+        &self,
+        first_byte: u8,
+        byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
+        let second_byte = next_byte(byte_stream)?;
 
         let sr = Self::extract_sr(second_byte).into();
         let is_sr_dest = Self::is_dest_in_reg_field(first_byte);
-        let rm = Self::extract_rm(first_byte, second_byte, byte_stream).into();
-        Inst::with_operands(
+        let rm = Self::extract_rm(first_byte, second_byte, byte_stream)?.into();
+        Ok(Inst::with_operands(
             op,
             if is_sr_dest { sr } else { rm },
             if is_sr_dest { rm } else { sr },
-        )
+        ))
+    }
+}
+
+impl InstructionEncoder for SRRM {
+    fn encode(&self, opcode: &[u8], mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (Some(first), Some(second)) = (inst.first, inst.second) else {
+            return None;
+        };
+        let (is_sr_dest, sr, rm) = match (first, second) {
+            (Operand::SR(sr), rm) => (true, sr, rm),
+            (rm, Operand::SR(sr)) => (false, sr, rm),
+            _ => return None,
+        };
+        if !Self::allows_dest(opcode[0], mask[0], is_sr_dest) {
+            return None;
+        }
+        let (modrm, disp) = Self::emit_rm(rm, true)?;
+        let mut bytes = vec![
+            opcode[0] | Self::emit_dest(is_sr_dest),
+            Self::emit_sr(sr) | modrm,
+        ];
+        bytes.extend(disp);
+        Some(bytes)
     }
 }
 
@@ -53,11 +78,13 @@ mod tests {
     fn reg_to_sr() {
         let bytes: [u8; 2] = [0b10001110, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::SR(SegmentRegister::ES),
@@ -70,11 +97,13 @@ mod tests {
     fn sr_to_reg() {
         let bytes: [u8; 2] = [0b10001100, 0b11000011];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::Mov
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::Mov
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::Mov,
                 Operand::Register(Register::BX),
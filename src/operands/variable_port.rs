@@ -1,7 +1,7 @@
 use crate::{
-    disasm::WithWideField,
+    disasm::{DecodeErrorKind, WithWideField},
     fields::{Operand, Operation, Register},
-    instruction::{Inst, InstructionDecoder},
+    instruction::{Inst, InstructionDecoder, InstructionEncoder},
     ByteStream,
 };
 
@@ -13,15 +13,40 @@ impl WithWideField for VariablePort {
 }
 
 impl InstructionDecoder for VariablePort {
-    fn decode(&self, first_byte: u8, _byte_stream: &mut ByteStream, op: Operation) -> Inst {
+    fn decode(
+        &self,
+        first_byte: u8,
+        _byte_stream: &mut ByteStream,
+        op: Operation,
+    ) -> Result<Inst, DecodeErrorKind> {
         let wide = Self::is_wide(first_byte);
         let acc = if wide { Register::AX } else { Register::AL }.into();
         let dx = Operand::Register(Register::DX);
-        Inst::with_operands(
+        Ok(Inst::with_operands(
             op,
             if op == Operation::IN { acc } else { dx },
             if op == Operation::IN { dx } else { acc },
-        )
+        ))
+    }
+}
+
+impl InstructionEncoder for VariablePort {
+    fn encode(&self, opcode: &[u8], _mask: &[u8], inst: &Inst) -> Option<Vec<u8>> {
+        let (acc, port) = if inst.operation == Operation::IN {
+            (inst.first, inst.second)
+        } else {
+            (inst.second, inst.first)
+        };
+        let (Some(Operand::Register(acc)), Some(Operand::Register(Register::DX))) = (acc, port)
+        else {
+            return None;
+        };
+        let wide = match acc {
+            Register::AX => true,
+            Register::AL => false,
+            _ => return None,
+        };
+        Some(vec![opcode[0] | Self::emit_wide(wide)])
     }
 }
 
@@ -35,11 +60,13 @@ mod tests {
     fn wide() {
         let bytes: [u8; 1] = [0b11101101];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::IN
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::IN
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::IN,
                 Operand::Register(Register::AX),
@@ -52,11 +79,13 @@ mod tests {
     fn not_wide() {
         let bytes: [u8; 1] = [0b11101110];
         assert_eq!(
-            DECODER.decode(
-                bytes[0],
-                &mut ByteStream::new(bytes[1..].iter()),
-                Operation::OUT
-            ),
+            DECODER
+                .decode(
+                    bytes[0],
+                    &mut ByteStream::new(bytes[1..].iter()),
+                    Operation::OUT
+                )
+                .unwrap(),
             Inst::with_operands(
                 Operation::OUT,
                 Operand::Register(Register::DX),
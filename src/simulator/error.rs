@@ -0,0 +1,74 @@
+use std::{fmt, io};
+
+use crate::{
+    cpu::Address,
+    disasm::{DecodeErrorKind, Instruction},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SimErrorKind {
+    /// the bytes at CS:IP aren't a valid instruction
+    Decode(DecodeErrorKind),
+    /// a valid instruction, or operand form of one, that isn't simulated
+    UnsupportedInstruction(String),
+    /// operands that don't make sense for the operation
+    InvalidOperands(String),
+    /// the program doesn't fit in the code segment from where it is loaded
+    AddressFault,
+    /// division by zero, or a quotient too large for the destination, with no handler for
+    /// interrupt 0
+    DivideError,
+    /// an interrupt whose vector table entry is null and that has no host hook
+    UnhandledInterrupt(u8),
+    /// writing the trace failed
+    Io(io::ErrorKind),
+}
+
+impl SimErrorKind {
+    pub fn unsupported(inst: &Instruction) -> Self {
+        Self::UnsupportedInstruction(inst.to_string())
+    }
+
+    pub fn invalid_operands(inst: &Instruction) -> Self {
+        Self::InvalidOperands(inst.to_string())
+    }
+}
+
+impl fmt::Display for SimErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(kind) => write!(f, "{}", kind),
+            Self::UnsupportedInstruction(inst) => write!(f, "unsupported instruction `{}`", inst),
+            Self::InvalidOperands(inst) => write!(f, "invalid operands in `{}`", inst),
+            Self::AddressFault => write!(f, "program doesn't fit in the code segment"),
+            Self::DivideError => write!(f, "divide error"),
+            Self::UnhandledInterrupt(vector) => write!(f, "no handler for interrupt {}", vector),
+            Self::Io(kind) => write!(f, "trace write error ({})", kind),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SimError {
+    pub kind: SimErrorKind,
+    /// CS:IP of the instruction that failed
+    pub address: Address,
+}
+
+impl SimError {
+    pub fn new(kind: SimErrorKind, address: Address) -> Self {
+        Self { kind, address }
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:04x}: {}",
+            self.address.segment, self.address.offset, self.kind
+        )
+    }
+}
+
+impl std::error::Error for SimError {}
@@ -0,0 +1,133 @@
+use std::{cell::RefCell, collections::VecDeque, ops::RangeInclusive, rc::Rc};
+
+/// Devices behind the IN and OUT instructions
+pub trait IoBus {
+    fn read_u8(&mut self, port: u16) -> u8;
+
+    fn write_u8(&mut self, port: u16, val: u8);
+
+    /// Reads `port` and `port + 1`, low byte first, unless the device handles words itself
+    fn read_u16(&mut self, port: u16) -> u16 {
+        u16::from_le_bytes([self.read_u8(port), self.read_u8(port.wrapping_add(1))])
+    }
+
+    /// Writes `port` and `port + 1`, low byte first, unless the device handles words itself
+    fn write_u16(&mut self, port: u16, val: u16) {
+        let [low, high] = val.to_le_bytes();
+        self.write_u8(port, low);
+        self.write_u8(port.wrapping_add(1), high);
+    }
+}
+
+/// An IN or OUT the bus saw, named after the `IoBus` method it went through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortAccess {
+    ReadU8 { port: u16, value: u8 },
+    ReadU16 { port: u16, value: u16 },
+    WriteU8 { port: u16, value: u8 },
+    WriteU16 { port: u16, value: u16 },
+}
+
+/// The most recent accesses a `PortBus` saw, shared with whoever asked for them
+#[derive(Clone)]
+pub struct PortLog {
+    accesses: Rc<RefCell<VecDeque<PortAccess>>>,
+    limit: usize,
+}
+
+impl PortLog {
+    pub fn accesses(&self) -> Vec<PortAccess> {
+        self.accesses.borrow().iter().copied().collect()
+    }
+
+    pub fn take(&self) -> Vec<PortAccess> {
+        self.accesses.borrow_mut().drain(..).collect()
+    }
+
+    fn push(&self, access: PortAccess) {
+        let mut accesses = self.accesses.borrow_mut();
+        if accesses.len() == self.limit {
+            accesses.pop_front();
+        }
+        if self.limit > 0 {
+            accesses.push_back(access);
+        }
+    }
+}
+
+/// The simulator's default bus: routes each access to the device attached on the port. Ports
+/// nothing is attached to read as all ones and ignore writes.
+#[derive(Default)]
+pub struct PortBus {
+    devices: Vec<(RangeInclusive<u16>, Box<dyn IoBus>)>,
+    log: Option<PortLog>,
+}
+
+impl PortBus {
+    /// Attaches `device` to `ports`, ahead of any device attached earlier on the same ports
+    pub fn attach(&mut self, ports: RangeInclusive<u16>, device: impl IoBus + 'static) {
+        self.devices.insert(0, (ports, Box::new(device)));
+    }
+
+    /// Starts keeping the last `limit` accesses, in a log that stays readable once the bus is
+    /// handed to the simulator
+    pub fn log_accesses(&mut self, limit: usize) -> PortLog {
+        let log = PortLog {
+            accesses: Rc::new(RefCell::new(VecDeque::with_capacity(limit))),
+            limit,
+        };
+        self.log = Some(log.clone());
+        log
+    }
+
+    fn device(&mut self, port: u16) -> Option<&mut Box<dyn IoBus>> {
+        self.devices
+            .iter_mut()
+            .find(|(ports, _)| ports.contains(&port))
+            .map(|(_, device)| device)
+    }
+
+    fn record(&self, access: PortAccess) {
+        if let Some(log) = &self.log {
+            log.push(access);
+        }
+    }
+}
+
+impl Default for Box<dyn IoBus> {
+    fn default() -> Self {
+        Box::new(PortBus::default())
+    }
+}
+
+impl IoBus for PortBus {
+    fn read_u8(&mut self, port: u16) -> u8 {
+        let value = self
+            .device(port)
+            .map_or(0xff, |device| device.read_u8(port));
+        self.record(PortAccess::ReadU8 { port, value });
+        value
+    }
+
+    fn write_u8(&mut self, port: u16, val: u8) {
+        if let Some(device) = self.device(port) {
+            device.write_u8(port, val);
+        }
+        self.record(PortAccess::WriteU8 { port, value: val });
+    }
+
+    fn read_u16(&mut self, port: u16) -> u16 {
+        let value = self
+            .device(port)
+            .map_or(0xffff, |device| device.read_u16(port));
+        self.record(PortAccess::ReadU16 { port, value });
+        value
+    }
+
+    fn write_u16(&mut self, port: u16, val: u16) {
+        if let Some(device) = self.device(port) {
+            device.write_u16(port, val);
+        }
+        self.record(PortAccess::WriteU16 { port, value: val });
+    }
+}
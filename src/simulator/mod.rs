@@ -0,0 +1,1465 @@
+mod error;
+mod io;
+mod step;
+mod stop;
+
+pub use error::*;
+pub use io::*;
+pub use step::*;
+pub use stop::*;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    ops::Range,
+};
+
+use crate::{
+    conditional_advance,
+    cpu::{
+        Address, Clocks8086, Clocks8088, DecodeCache, Flags, JmpNotTakenClocks, JmpTakenClocks,
+        Memory, MemoryWrite, RegisterChange, Registers, UndefinedFlags,
+    },
+    disasm::{decode_next, Instruction, Program},
+    fields::{Data, EffectiveAddress, Inc, Operand, Operation, SegmentRegister},
+    handlers::*,
+};
+
+/// Bytes taken by the interrupt vector table at 0000:0000, four for each of the 256 vectors
+const VECTOR_TABLE_SIZE: u32 = 256 * 4;
+
+/// Host code run in place of an emulated interrupt handler
+pub type InterruptHook = Box<dyn FnMut(&mut Registers, &mut Flags, &mut Memory)>;
+
+#[derive(Default)]
+pub struct Simulator {
+    pub registers: Registers,
+    pub flags: Flags,
+    pub ip: u16,
+    log_ip: bool,
+    estimate_cycles: bool,
+    cycles_8086: Clocks8086,
+    cycles_8088: Clocks8088,
+    pub memory: Memory,
+    /// devices behind IN and OUT, a `PortBus` with nothing attached unless replaced
+    pub io: Box<dyn IoBus>,
+    /// physical addresses the loaded program occupies
+    code: Range<u32>,
+    /// the host set up the vector table, so it's used even with code loaded over it
+    vector_table: bool,
+    decode_cache: Option<DecodeCache>,
+    stop_conditions: StopConditions,
+    /// calls made minus returns taken
+    call_depth: usize,
+    trace: Option<Box<dyn std::io::Write>>,
+    undefined_flags: UndefinedFlags,
+    /// interrupts raised by the host, taken between instructions while IF is set
+    pending_interrupts: VecDeque<u8>,
+    interrupt_hooks: HashMap<u8, InterruptHook>,
+}
+
+impl Simulator {
+    pub fn enable_ip_log(&mut self) {
+        self.log_ip = true;
+    }
+
+    pub fn enable_cycle_estimation(&mut self) {
+        self.estimate_cycles = true;
+    }
+
+    /// Writes a line for every instruction executed, with the registers, flags and memory it
+    /// changed, and its clocks when cycle estimation is enabled
+    pub fn enable_trace(&mut self, out: impl std::io::Write + 'static) {
+        self.trace = Some(Box::new(out));
+    }
+
+    /// Chooses what happens to flags an instruction leaves undefined
+    pub fn set_undefined_flags(&mut self, policy: UndefinedFlags) {
+        self.undefined_flags = policy;
+    }
+
+    /// Queues a hardware interrupt, taken before the next instruction once IF is set
+    pub fn raise_interrupt(&mut self, vector: u8) {
+        self.pending_interrupts.push_back(vector);
+    }
+
+    /// Runs `hook` instead of the handler in the vector table whenever interrupt `vector` is
+    /// taken. Execution carries on after the instruction that raised it.
+    pub fn hook_interrupt(
+        &mut self,
+        vector: u8,
+        hook: impl FnMut(&mut Registers, &mut Flags, &mut Memory) + 'static,
+    ) {
+        self.interrupt_hooks.insert(vector, Box::new(hook));
+        self.vector_table = true;
+    }
+
+    /// Points interrupt `vector` at `handler` in the vector table
+    pub fn set_interrupt_vector(&mut self, vector: u8, handler: Address) {
+        let entry = Address::new(0, vector as u16 * 4);
+        self.memory.store_16(entry, handler.offset);
+        self.memory.store_16(entry.offset_by(2), handler.segment);
+        self.commit_writes();
+        self.vector_table = true;
+    }
+
+    /// Takes interrupts through the vector table even when the program was loaded over it.
+    /// Otherwise a program at 0000:0000 gets `DivideError` and `UnhandledInterrupt` rather than
+    /// jumping to addresses made of its own code bytes.
+    pub fn enable_vector_table(&mut self) {
+        self.vector_table = true;
+    }
+
+    /// Replaces the conditions `exec` stops on. A cycle limit turns on cycle estimation.
+    pub fn set_stop_conditions(&mut self, conditions: impl IntoIterator<Item = StopCondition>) {
+        self.stop_conditions = StopConditions(conditions.into_iter().collect());
+        if self.stop_conditions.counts_cycles() {
+            self.enable_cycle_estimation();
+        }
+    }
+
+    /// Adds a condition for `exec` to stop on. A cycle limit turns on cycle estimation.
+    pub fn stop_when(&mut self, condition: StopCondition) {
+        if !self.stop_conditions.contains(condition) {
+            self.stop_conditions.0.push(condition);
+        }
+        if self.stop_conditions.counts_cycles() {
+            self.enable_cycle_estimation();
+        }
+    }
+
+    /// Keeps decoded instructions around by address. Entries are dropped whenever memory they
+    /// were decoded from is written to, so self-modifying code still behaves.
+    pub fn enable_decode_cache(&mut self) {
+        self.decode_cache = Some(DecodeCache::default());
+    }
+
+    pub fn clocks_8086(&self) -> usize {
+        self.cycles_8086.0
+    }
+
+    pub fn clocks_8088(&self) -> usize {
+        self.cycles_8088.0
+    }
+
+    /// Copies the program into memory at CS:IP
+    pub fn load(&mut self, program: &Program) -> Result<(), SimError> {
+        let code_end = self.ip as usize + program.bytes().len();
+        if code_end > 1 << 16 {
+            return Err(SimError::new(SimErrorKind::AddressFault, self.code_addr()));
+        }
+        self.memory.load(self.code_addr(), program.bytes());
+        self.commit_writes();
+        let start = self.code_addr().physical();
+        self.code = start..start + program.bytes().len() as u32;
+        Ok(())
+    }
+
+    /// Points CS:IP at `cs:ip` and loads the program there, keeping it clear of data the
+    /// program writes near the bottom of memory
+    pub fn load_at(&mut self, cs: u16, ip: u16, program: &Program) -> Result<(), SimError> {
+        self.registers
+            .set_sr_imd(SegmentRegister::CS, Data::U16(cs));
+        self.ip = ip;
+        self.load(program)
+    }
+
+    /// Memory address of the next instruction
+    fn code_addr(&self) -> Address {
+        Address::new(self.registers.cs(), self.ip)
+    }
+
+    fn fetch(&mut self) -> Result<Instruction, SimError> {
+        // whatever the host stored since the last instruction isn't part of the next one's writes
+        self.commit_writes();
+        let addr = self.code_addr();
+        if let Some(inst) = self
+            .decode_cache
+            .as_ref()
+            .and_then(|c| c.get(addr.physical()))
+        {
+            return Ok(inst.clone());
+        }
+        let inst: Instruction = decode_next(self.memory.slice_from(addr), 0)
+            .map_err(|err| SimError::new(SimErrorKind::Decode(err.kind), addr))?
+            .try_into()
+            .expect("decoded instruction has size");
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.insert(addr.physical(), inst.clone());
+        }
+        Ok(inst)
+    }
+
+    /// Whether 0000:0000 holds interrupt vectors rather than the program's code
+    fn uses_vector_table(&self) -> bool {
+        self.vector_table || self.code.is_empty() || self.code.start >= VECTOR_TABLE_SIZE
+    }
+
+    fn has_interrupt_handler(&self, vector: u8) -> bool {
+        self.interrupt_hooks.contains_key(&vector)
+            || (self.uses_vector_table()
+                && interrupt_vector(&self.memory, vector) != Address::new(0, 0))
+    }
+
+    /// Takes interrupt `vector`, through the host hook if there is one
+    fn interrupt(&mut self, vector: u8) -> Result<(), SimErrorKind> {
+        if let Some(hook) = self.interrupt_hooks.get_mut(&vector) {
+            hook(&mut self.registers, &mut self.flags, &mut self.memory);
+            return Ok(());
+        }
+        if !self.has_interrupt_handler(vector) {
+            return Err(SimErrorKind::UnhandledInterrupt(vector));
+        }
+        enter_interrupt(
+            interrupt_vector(&self.memory, vector),
+            &mut self.ip,
+            &mut self.registers,
+            &mut self.flags,
+            &mut self.memory,
+        );
+        Ok(())
+    }
+
+    /// Takes the oldest pending hardware interrupt, unless IF masks it
+    fn service_interrupts(&mut self) -> Result<(), SimError> {
+        if !self.flags.interrupt {
+            return Ok(());
+        }
+        if let Some(vector) = self.pending_interrupts.pop_front() {
+            let addr = self.code_addr();
+            self.interrupt(vector)
+                .map_err(|kind| SimError::new(kind, addr))?;
+            self.commit_writes();
+        }
+        Ok(())
+    }
+
+    /// Drops cached instructions decoded from memory written since the last call
+    fn commit_writes(&mut self) -> Vec<MemoryWrite> {
+        let writes = self.memory.take_writes();
+        if let Some(cache) = self.decode_cache.as_mut() {
+            writes
+                .iter()
+                .for_each(|write| cache.invalidate(write.address));
+        }
+        writes
+    }
+
+    /// Stop conditions that hold before fetching the next instruction
+    fn stop_before_fetch(&self, executed: usize) -> Option<StopReason> {
+        self.stop_conditions
+            .0
+            .iter()
+            .find_map(|&condition| match condition {
+                StopCondition::EndOfCode if !self.code.contains(&self.code_addr().physical()) => {
+                    Some(StopReason::EndOfCode)
+                }
+                StopCondition::Instructions(n) if executed >= n => {
+                    Some(StopReason::InstructionLimit(n))
+                }
+                StopCondition::Address(addr)
+                    if executed > 0 && self.code_addr().physical() == addr.physical() =>
+                {
+                    Some(StopReason::ReachedAddress(addr))
+                }
+                StopCondition::Cycles(n) if self.cycles_8086.0 >= n => {
+                    Some(StopReason::CycleLimit(n))
+                }
+                _ => None,
+            })
+    }
+
+    /// Runs the loaded program, fetching each instruction from memory at ip, until one of the
+    /// stop conditions holds
+    pub fn exec(&mut self) -> Result<StopReason, SimError> {
+        let mut executed = 0;
+        loop {
+            self.service_interrupts()?;
+            if let Some(reason) = self.stop_before_fetch(executed) {
+                return Ok(reason);
+            }
+            let inst = self.fetch()?;
+            let is_return = matches!(inst.operation, Operation::Ret | Operation::RetFar);
+            if is_return
+                && self.call_depth == 0
+                && self
+                    .stop_conditions
+                    .contains(StopCondition::OutermostReturn)
+            {
+                return Ok(StopReason::Returned);
+            }
+            let step = self.execute(inst)?;
+            executed += 1;
+            if step.instruction.operation == Operation::HLT
+                && self.stop_conditions.contains(StopCondition::Halt)
+            {
+                return Ok(StopReason::Halted);
+            }
+        }
+    }
+
+    /// Executes the instruction at CS:IP
+    pub fn step(&mut self) -> Result<StepResult, SimError> {
+        self.service_interrupts()?;
+        let inst = self.fetch()?;
+        self.execute(inst)
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<StepResult, SimError> {
+        let address = self.code_addr();
+        let registers_before = self.registers.clone();
+        let flags_before = self.flags;
+        let clocks_before = (self.cycles_8086.0, self.cycles_8088.0);
+        let inst = &instruction;
+
+        let fail = |kind| SimError::new(kind, address);
+        let clocks = if self.estimate_cycles && !inst.is_conditional_advance() {
+            let (clocks86, clocks88) = inst
+                .clocks(|ea: EffectiveAddress| -> bool {
+                    !self.registers.calculate_eff_addr(ea).is_multiple_of(2)
+                })
+                .map_err(fail)?;
+            let per_bit = inst.clocks_per_bit() * self.registers.cx() as u8 as usize;
+            Some((
+                Clocks8086(clocks86.0 + per_bit),
+                Clocks8088(clocks88.0 + per_bit),
+            ))
+        } else {
+            None
+        };
+
+        let cx = self.registers.cx();
+        self.ip = self.ip.wrapping_add(inst.size as u16);
+        if let Err(kind) = self.dispatch(inst) {
+            self.ip = address.offset;
+            return Err(fail(kind));
+        }
+        self.flags = Flags::apply(
+            flags_before,
+            self.flags,
+            inst.flag_effects(cx as u8),
+            self.undefined_flags,
+        );
+        // single step trap, once TF has been set for a whole instruction
+        if flags_before.trap && self.flags.trap {
+            self.interrupt(1).map_err(fail)?;
+        }
+        if let Some((clocks86, clocks88)) = clocks {
+            // repeated string instructions count CX down once per iteration
+            let reps = cx.wrapping_sub(self.registers.cx()) as usize;
+            let (per_rep86, per_rep88) = inst.clocks_per_rep();
+            self.cycles_8086 += Clocks8086(clocks86.0 + per_rep86.0 * reps);
+            self.cycles_8088 += Clocks8088(clocks88.0 + per_rep88.0 * reps);
+        }
+
+        let writes = self.commit_writes();
+
+        let mut registers = registers_before.changes(&self.registers);
+        registers.push(RegisterChange {
+            name: "ip",
+            old: address.offset,
+            new: self.ip,
+        });
+        let cycles = self.estimate_cycles.then(|| StepCycles {
+            clocks_8086: self.cycles_8086.0 - clocks_before.0,
+            clocks_8088: self.cycles_8088.0 - clocks_before.1,
+            total_8086: self.cycles_8086.0,
+            ea: [inst.first, inst.second]
+                .into_iter()
+                .find_map(|operand| match operand {
+                    Some(Operand::EffectiveAddress(ea)) => Some(ea.clocks()),
+                    _ => None,
+                }),
+        });
+        let step = StepResult {
+            instruction,
+            address,
+            registers,
+            flags: (flags_before != self.flags).then_some((flags_before, self.flags)),
+            memory: writes,
+            cycles,
+        };
+        if let Some(trace) = self.trace.as_mut() {
+            writeln!(trace, "{}", step).map_err(|err| fail(SimErrorKind::Io(err.kind())))?;
+        }
+        Ok(step)
+    }
+
+    /// Runs the handler for the instruction, with ip already past it
+    fn dispatch(&mut self, inst: &Instruction) -> Result<(), SimErrorKind> {
+        match inst.operation {
+            Operation::Mov => handle_mov(inst, &mut self.registers, &mut self.memory)?,
+            Operation::Add => handle_arithmetic(
+                ArithmeticOp::Add,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::ADC => handle_arithmetic(
+                ArithmeticOp::Adc,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::SBB => handle_arithmetic(
+                ArithmeticOp::Sbb,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::NEG => handle_arithmetic(
+                ArithmeticOp::Neg,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::MUL => handle_multiply(
+                MultiplyOp::Mul,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::IMUL => handle_multiply(
+                MultiplyOp::Imul,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::DIV | Operation::IDIV => {
+                let op = if inst.operation == Operation::DIV {
+                    MultiplyOp::Div
+                } else {
+                    MultiplyOp::Idiv
+                };
+                match handle_multiply(
+                    op,
+                    inst,
+                    &mut self.registers,
+                    &mut self.flags,
+                    &mut self.memory,
+                ) {
+                    // like the 8086, the handler returns past the division
+                    Err(SimErrorKind::DivideError) if self.has_interrupt_handler(0) => {
+                        self.interrupt(0)?
+                    }
+                    result => result?,
+                }
+            }
+            Operation::Sub => handle_arithmetic(
+                ArithmeticOp::Sub,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::Cmp => handle_arithmetic(
+                ArithmeticOp::Cmp,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::JE => conditional_advance!(self.flags.zero, self, inst),
+            Operation::JNE => conditional_advance!(!self.flags.zero, self, inst),
+            Operation::JL => {
+                conditional_advance!(self.flags.sign != self.flags.overflow, self, inst)
+            }
+            Operation::JNL => {
+                conditional_advance!(self.flags.sign == self.flags.overflow, self, inst)
+            }
+            Operation::JLE => {
+                let cond = self.flags.zero || self.flags.sign != self.flags.overflow;
+                conditional_advance!(cond, self, inst);
+            }
+            Operation::JNLE => {
+                let cond = !self.flags.zero && self.flags.sign == self.flags.overflow;
+                conditional_advance!(cond, self, inst);
+            }
+            Operation::JB => conditional_advance!(self.flags.carry, self, inst),
+            Operation::JNB => conditional_advance!(!self.flags.carry, self, inst),
+            Operation::JBE => {
+                conditional_advance!(self.flags.carry || self.flags.zero, self, inst)
+            }
+            Operation::JNBE => {
+                conditional_advance!(!self.flags.carry && !self.flags.zero, self, inst)
+            }
+            Operation::JP => conditional_advance!(self.flags.parity, self, inst),
+            Operation::JNP => conditional_advance!(!self.flags.parity, self, inst),
+            Operation::JO => conditional_advance!(self.flags.overflow, self, inst),
+            Operation::JNO => conditional_advance!(!self.flags.overflow, self, inst),
+            Operation::JS => conditional_advance!(self.flags.sign, self, inst),
+            Operation::JNS => conditional_advance!(!self.flags.sign, self, inst),
+            Operation::JCXZ => conditional_advance!(self.registers.cx() == 0, self, inst),
+            Operation::LOOP => {
+                self.registers.dec_cx();
+                let cond = self.registers.cx() != 0;
+                conditional_advance!(cond, self, inst);
+            }
+            Operation::LOOPZ => {
+                self.registers.dec_cx();
+                let cond = self.registers.cx() != 0 && self.flags.zero;
+                conditional_advance!(cond, self, inst);
+            }
+            Operation::LOOPNZ => {
+                self.registers.dec_cx();
+                let cond = self.registers.cx() != 0 && !self.flags.zero;
+                conditional_advance!(cond, self, inst);
+            }
+            Operation::Jmp | Operation::JmpFar => {
+                handle_jmp(inst, &mut self.ip, &mut self.registers, &self.memory)?
+            }
+            Operation::TEST => handle_logical(
+                LogicalOp::Test,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::XOR => handle_logical(
+                LogicalOp::Xor,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::AND => handle_logical(
+                LogicalOp::And,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::OR => handle_logical(
+                LogicalOp::Or,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::NOT => handle_logical(
+                LogicalOp::Not,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::INC => handle_arithmetic(
+                ArithmeticOp::Inc,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::DEC => handle_arithmetic(
+                ArithmeticOp::Dec,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::SHL => handle_shift(
+                ShiftOp::Shl,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::SHR => handle_shift(
+                ShiftOp::Shr,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::SAR => handle_shift(
+                ShiftOp::Sar,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::ROL => handle_shift(
+                ShiftOp::Rol,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::ROR => handle_shift(
+                ShiftOp::Ror,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::RCL => handle_shift(
+                ShiftOp::Rcl,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::RCR => handle_shift(
+                ShiftOp::Rcr,
+                inst,
+                &mut self.registers,
+                &mut self.flags,
+                &mut self.memory,
+            )?,
+            Operation::Push => handle_push(inst, &mut self.registers, &mut self.memory)?,
+            Operation::Pop => handle_pop(inst, &mut self.registers, &mut self.memory)?,
+            Operation::Call | Operation::CallFar => {
+                handle_call(inst, &mut self.ip, &mut self.registers, &mut self.memory)?;
+                self.call_depth += 1;
+            }
+            Operation::Ret | Operation::RetFar => {
+                handle_ret(inst, &mut self.ip, &mut self.registers, &self.memory);
+                self.call_depth = self.call_depth.saturating_sub(1);
+            }
+            Operation::PUSHF
+            | Operation::POPF
+            | Operation::LAHF
+            | Operation::SAHF
+            | Operation::CLC
+            | Operation::STC
+            | Operation::CMC
+            | Operation::CLD
+            | Operation::STD
+            | Operation::CLI
+            | Operation::STI => {
+                handle_flags(inst, &mut self.registers, &mut self.flags, &mut self.memory)?
+            }
+            Operation::MOVSB
+            | Operation::MOVSW
+            | Operation::LODSB
+            | Operation::LODSW
+            | Operation::STOSB
+            | Operation::STOSW
+            | Operation::CMPSB
+            | Operation::CMPSW
+            | Operation::SCASB
+            | Operation::SCASW => {
+                handle_string(inst, &mut self.registers, &mut self.flags, &mut self.memory)?
+            }
+            Operation::INT => match inst.first {
+                Some(Operand::Immediate(Data::U8(vector))) => self.interrupt(vector)?,
+                _ => return Err(SimErrorKind::invalid_operands(inst)),
+            },
+            Operation::INT3 => self.interrupt(3)?,
+            Operation::INTO => {
+                if self.flags.overflow {
+                    self.interrupt(4)?;
+                }
+            }
+            Operation::IRET => handle_iret(
+                &mut self.ip,
+                &mut self.registers,
+                &mut self.flags,
+                &self.memory,
+            ),
+            Operation::IN | Operation::OUT => {
+                handle_io(inst, &mut self.registers, self.io.as_mut())?
+            }
+            Operation::HLT => {}
+            _ => return Err(SimErrorKind::unsupported(inst)),
+        }
+        Ok(())
+    }
+
+    pub fn dump_memory(&self, mut f: impl std::io::Write) -> Result<(), std::io::Error> {
+        f.write_all(self.memory.raw())
+    }
+}
+
+impl Display for Simulator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.registers)?;
+        if self.log_ip {
+            writeln!(f, "      ip: {:#06x} ({})", self.ip, self.ip)?;
+        }
+        write!(f, "{}", self.flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assemble_8086,
+        fields::{Register, SegmentRegister},
+        instruction::Inst,
+    };
+
+    use super::*;
+
+    #[test]
+    fn simulator_move_immediate_to_register() {
+        let mut mov = Inst::with_operands_v2(Operation::Mov, Register::BX, Data::U16(256));
+        mov.set_size(3);
+        let mut simulator = Simulator::default();
+        simulator.load(&vec![mov].try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(256));
+    }
+
+    #[test]
+    fn simulator_decode_cache_sees_self_modifying_code() {
+        let instructions = assemble_8086(
+            "mov cx, 2\n\
+             top:\n\
+             mov bx, 7\n\
+             mov word [top + 1], 42\n\
+             loop top",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_decode_cache();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(42));
+    }
+
+    #[test]
+    fn simulator_decode_cache_sees_host_writes() {
+        let program: Program = assemble_8086("mov bx, 7").unwrap().try_into().unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_decode_cache();
+        simulator.load(&program).unwrap();
+        simulator.exec().unwrap();
+        // patch the immediate behind the simulator's back and run the cached address again
+        simulator.memory.load(Address::new(0, 1), &[42]);
+        simulator.ip = 0;
+        let step = simulator.step().unwrap();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(42));
+        assert!(step.memory.is_empty());
+    }
+
+    #[test]
+    fn simulator_honours_segment_override() {
+        let instructions = assemble_8086(
+            "mov ax, 0x2000\n\
+             mov es, ax\n\
+             mov word es:[4], 7\n\
+             mov word [4], 9",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator
+            .load_at(0x1000, 0, &instructions.try_into().unwrap())
+            .unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.memory.raw()[0x20004], 7);
+        assert_eq!(simulator.memory.raw()[0x00004], 9);
+    }
+
+    #[test]
+    fn simulator_calls_and_returns() {
+        let instructions = assemble_8086(
+            "mov sp, 0x100\n\
+             mov ax, 3\n\
+             push ax\n\
+             call double\n\
+             pop bx\n\
+             ret\n\
+             double:\n\
+             mov bp, sp\n\
+             mov cx, [bp + 2]\n\
+             add cx, cx\n\
+             push cx\n\
+             pop dx\n\
+             ret 2",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator
+            .load_at(0x1000, 0, &instructions.try_into().unwrap())
+            .unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::Returned);
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(6));
+        // `ret 2` dropped the argument, so `pop bx` reads below the initial stack
+        assert_eq!(simulator.registers.get(Register::SP), Data::U16(0x102));
+        assert_eq!(simulator.ip, 11);
+    }
+
+    #[test]
+    fn simulator_jumps() {
+        let instructions = assemble_8086(
+            "mov bx, indirect\n\
+             jmp near skip\n\
+             hlt\n\
+             skip:\n\
+             jmp bx\n\
+             hlt\n\
+             indirect:\n\
+             mov word [0x200], memory\n\
+             jmp word [0x200]\n\
+             hlt\n\
+             memory:\n\
+             mov word [0x204], far\n\
+             mov word [0x206], 0\n\
+             jmp far [0x204]\n\
+             hlt\n\
+             far:\n\
+             jmp 0:direct\n\
+             hlt\n\
+             direct:\n\
+             jmp short done\n\
+             hlt\n\
+             done:\n\
+             hlt",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        let clocks: Vec<usize> = (0..11)
+            .map(|_| simulator.step().unwrap().cycles.unwrap().clocks_8086)
+            .collect();
+        // near and short 15, register 11, memory 18 + EA, far through memory 24 + EA, far
+        // direct 15
+        assert_eq!(clocks, [4, 15, 11, 16, 24, 16, 16, 30, 15, 15, 2]);
+        assert_eq!(simulator.ip, 48);
+    }
+
+    #[test]
+    fn simulator_conditional_jumps() {
+        // every jump that isn't taken lands on a `push`, which leaves the flags alone, every one
+        // that is skips an `inc dx`
+        let program: Program = assemble_8086(
+            "mov sp, 0x400\n\
+             mov ax, -1\n\
+             cmp ax, 1\n\
+             jl a\n\
+             inc dx\n\
+             a: jb b\n\
+             push ax\n\
+             b: jnbe c\n\
+             inc dx\n\
+             c: jnl d\n\
+             push ax\n\
+             d: jle e\n\
+             inc dx\n\
+             e: jbe f\n\
+             push ax\n\
+             f: jnb g\n\
+             inc dx\n\
+             g: jnle h\n\
+             push ax\n\
+             h: js i\n\
+             inc dx\n\
+             i: jns j\n\
+             push ax\n\
+             j: jo k\n\
+             push ax\n\
+             k: jno l\n\
+             inc dx\n\
+             l: jnp m\n\
+             inc dx\n\
+             m: mov cx, 0\n\
+             jcxz n\n\
+             inc dx\n\
+             n: mov cx, 3\n\
+             o: inc si\n\
+             cmp si, 1\n\
+             loopz o\n\
+             hlt",
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&program).unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::Halted);
+        assert_eq!(
+            simulator.registers.get(Register::SP),
+            Data::U16(0x400 - 6 * 2)
+        );
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(0));
+        // LOOPZ went around once, then stopped on ZF with CX left at 1
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(2));
+        assert_eq!(simulator.registers.get(Register::CX), Data::U16(1));
+    }
+
+    #[test]
+    fn simulator_stop_conditions() {
+        let program: Program = assemble_8086(
+            "mov cx, 3\n\
+             top:\n\
+             inc bx\n\
+             loop top\n\
+             hlt\n\
+             inc dx",
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+
+        let mut simulator = Simulator::default();
+        simulator.load(&program).unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::Halted);
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(3));
+        assert_eq!(simulator.ip, 7);
+
+        let mut simulator = Simulator::default();
+        simulator.set_stop_conditions([StopCondition::Instructions(4)]);
+        simulator.load(&program).unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::InstructionLimit(4));
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(2));
+
+        let mut simulator = Simulator::default();
+        simulator.set_stop_conditions([StopCondition::Address(Address::new(0, 3))]);
+        simulator.stop_when(StopCondition::EndOfCode);
+        simulator.load(&program).unwrap();
+        assert_eq!(
+            simulator.exec().unwrap(),
+            StopReason::ReachedAddress(Address::new(0, 3))
+        );
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(0));
+
+        let mut simulator = Simulator::default();
+        simulator.set_stop_conditions([StopCondition::EndOfCode]);
+        simulator.load(&program).unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::EndOfCode);
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(1));
+
+        // counts clocks without cycle estimation having been asked for
+        let mut simulator = Simulator::default();
+        simulator.set_stop_conditions([StopCondition::Cycles(5)]);
+        simulator.load(&program).unwrap();
+        assert_eq!(simulator.exec().unwrap(), StopReason::CycleLimit(5));
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(1));
+        assert_eq!(simulator.clocks_8086(), 6);
+    }
+
+    #[test]
+    fn simulator_end_of_code_follows_segments() {
+        // the first jump lands on the next instruction through another segment, the second
+        // leaves the program
+        let instructions = assemble_8086(
+            "jmp 0x0ff0:0x105\n\
+             mov ax, 1\n\
+             jmp 0x1100:0\n\
+             hlt",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator
+            .load_at(0x1000, 0, &instructions.try_into().unwrap())
+            .unwrap();
+        assert_eq!(simulator.exec(), Ok(StopReason::EndOfCode));
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(1));
+        assert_eq!(simulator.code_addr(), Address::new(0x1100, 0));
+    }
+
+    #[test]
+    fn simulator_step_reports_changes() {
+        let instructions = assemble_8086(
+            "mov cx, 200\n\
+             mov word [bx + 4], cx\n\
+             cmp cx, 200",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+
+        let step = simulator.step().unwrap();
+        assert_eq!(step.address, Address::new(0, 0));
+        assert_eq!(
+            step.registers,
+            [
+                RegisterChange {
+                    name: "cx",
+                    old: 0,
+                    new: 200
+                },
+                RegisterChange {
+                    name: "ip",
+                    old: 0,
+                    new: 3
+                }
+            ]
+        );
+        assert!(step.flags.is_none());
+
+        let step = simulator.step().unwrap();
+        assert_eq!(step.memory.len(), 2);
+        assert_eq!((step.memory[0].address, step.memory[0].new), (4, 200));
+        let cycles = step.cycles.unwrap();
+        assert_eq!((cycles.clocks_8086, cycles.ea), (18, Some(9)));
+
+        let step = simulator.step().unwrap();
+        let (before, after) = step.flags.unwrap();
+        assert!(!before.zero && after.zero);
+    }
+
+    #[test]
+    fn simulator_reports_unsupported_instruction() {
+        let instructions = assemble_8086("mov ax, 1\naaa").unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        let err = simulator.exec().unwrap_err();
+        assert_eq!(
+            err,
+            SimError::new(
+                SimErrorKind::UnsupportedInstruction("aaa".to_string()),
+                Address::new(0, 3)
+            )
+        );
+        assert_eq!(err.to_string(), "0000:0003: unsupported instruction `aaa`");
+        assert_eq!(simulator.ip, 3);
+    }
+
+    #[test]
+    fn simulator_multiword_arithmetic() {
+        let instructions = assemble_8086(
+            "mov ax, 0xffff\n\
+             mov dx, 1\n\
+             add ax, 1\n\
+             adc dx, 0\n\
+             mov bx, 0\n\
+             mov cx, 1\n\
+             sub bx, 1\n\
+             sbb cx, 0\n\
+             mov si, 0x8000\n\
+             neg si",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(0));
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(2));
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(0xffff));
+        assert_eq!(simulator.registers.get(Register::CX), Data::U16(0));
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(0x8000));
+        assert!(simulator.flags.carry && simulator.flags.overflow);
+    }
+
+    #[test]
+    fn simulator_multiply_and_divide() {
+        let instructions = assemble_8086(
+            "mov ax, 300\n\
+             mov bx, 1000\n\
+             mul bx\n\
+             mov cx, 7\n\
+             div cx\n\
+             mov si, dx\n\
+             mov ax, -7\n\
+             mov bl, 2\n\
+             idiv bl\n\
+             mov di, ax\n\
+             mov al, -3\n\
+             mov cl, 5\n\
+             imul cl",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        // 300000 / 7 = 42857 remainder 1
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(1));
+        // -7 / 2 = -3 remainder -1
+        assert_eq!(simulator.registers.get(Register::DI), Data::U16(0xfffd));
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(0xfff1));
+        assert!(!simulator.flags.carry && !simulator.flags.overflow);
+    }
+
+    #[test]
+    fn simulator_divide_error() {
+        let instructions = assemble_8086("mov ax, 0x1000\nmov bl, 2\ndiv bl").unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        let err = simulator.exec().unwrap_err();
+        assert_eq!(err.kind, SimErrorKind::DivideError);
+        assert_eq!(err.address, Address::new(0, 5));
+    }
+
+    #[test]
+    fn simulator_inc_keeps_carry_between_adc() {
+        let instructions = assemble_8086(
+            "mov ax, 0xffff\n\
+             add ax, 1\n\
+             inc si\n\
+             dec di\n\
+             adc bx, 0",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(1));
+    }
+
+    #[test]
+    fn simulator_flag_instructions() {
+        let instructions = assemble_8086(
+            "mov sp, 0x200\n\
+             stc\n\
+             std\n\
+             pushf\n\
+             cmc\n\
+             cld\n\
+             lahf\n\
+             pop bx\n\
+             push bx\n\
+             popf\n\
+             mov ah, 0x40\n\
+             sahf",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(0xf403));
+        // SAHF loaded ZF and cleared CF, POPF brought back DF
+        assert!(simulator.flags.zero && !simulator.flags.carry && simulator.flags.direction);
+        assert_eq!(simulator.registers.get(Register::SP), Data::U16(0x200));
+    }
+
+    #[test]
+    fn simulator_string_instructions() {
+        let instructions = assemble_8086(
+            "mov ax, 0x1122\n\
+             mov di, 0x100\n\
+             mov cx, 3\n\
+             rep stosw\n\
+             mov si, 0x100\n\
+             mov di, 0x200\n\
+             mov cx, 3\n\
+             rep movsw\n\
+             mov byte [0x203], 0x33\n\
+             mov si, 0x100\n\
+             mov di, 0x200\n\
+             mov cx, 6\n\
+             repe cmpsb\n\
+             mov bx, cx\n\
+             std\n\
+             mov di, 0x205\n\
+             mov al, 0x33\n\
+             mov cx, 6\n\
+             repne scasb\n\
+             lodsb",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator
+            .load_at(0x1000, 0, &instructions.try_into().unwrap())
+            .unwrap();
+        simulator.exec().unwrap();
+        let word = |offset| simulator.memory.load_16(Address::new(0, offset));
+        assert_eq!(
+            [word(0x200), word(0x202), word(0x204)],
+            [0x1122, 0x3322, 0x1122]
+        );
+        // the compare stopped on the fourth byte
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(2));
+        // the scan found 0x33 on its third byte going down, leaving DI just below it
+        assert_eq!(simulator.registers.get(Register::DI), Data::U16(0x202));
+        assert_eq!(simulator.registers.get(Register::CX), Data::U16(3));
+        assert!(simulator.flags.zero);
+        // lodsb read [0x104] and stepped SI backwards
+        assert_eq!(simulator.registers.get(Register::AL), Data::U8(0x22));
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(0x103));
+    }
+
+    #[test]
+    fn simulator_repeated_string_with_segment_override() {
+        let setup = assemble_8086(
+            "mov ax, 0x20\n\
+             mov es, ax\n\
+             mov si, 0\n\
+             mov di, 0x300\n\
+             mov cx, 3",
+        )
+        .unwrap();
+        // rep es movsb, with the prefixes in either order
+        for prefixes in [[0xf3, 0x26], [0x26, 0xf3]] {
+            let mut bytes: Vec<u8> = setup.iter().flat_map(|i| i.bytes().to_vec()).collect();
+            bytes.extend(prefixes);
+            bytes.push(0xa4);
+            let mut simulator = Simulator::default();
+            simulator.memory.load(Address::new(0x20, 0), &[1, 2, 3]);
+            simulator.load_at(0x1000, 0, &bytes.into()).unwrap();
+            simulator.exec().unwrap();
+            // copied all three bytes, reading from ES rather than DS
+            assert_eq!(
+                simulator.memory.slice_from(Address::new(0x20, 0x300))[..3],
+                [1, 2, 3]
+            );
+            assert_eq!(simulator.registers.get(Register::CX), Data::U16(0));
+        }
+    }
+
+    #[test]
+    fn simulator_byte_memory_operands() {
+        let instructions = assemble_8086(
+            "mov si, 0x100\n\
+             mov byte [si], 0x7f\n\
+             mov al, [si]\n\
+             mov al, [0x100]\n\
+             mov [si + 2], al\n\
+             add byte [si], 1\n\
+             inc byte [si + 1]\n\
+             sub [si + 1], al\n\
+             mov es, [si]\n\
+             cmp byte [si], 0x80",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        let clocks: Vec<usize> = (0..10)
+            .map(|_| simulator.step().unwrap().cycles.unwrap().clocks_8086)
+            .collect();
+        // [si] costs 5 clocks of EA, [si + 2] 9, and the accumulator form with a direct
+        // address none
+        assert_eq!(clocks, [4, 15, 13, 10, 18, 22, 24, 25, 13, 15]);
+        assert_eq!(simulator.registers.get(Register::AL), Data::U8(0x7f));
+        assert_eq!(simulator.memory.load_16(Address::new(0, 0x100)), 0x8280);
+        assert_eq!(
+            simulator.registers.get_sr(SegmentRegister::ES),
+            Data::U16(0x8280)
+        );
+        assert!(simulator.flags.zero && !simulator.flags.overflow);
+    }
+
+    #[test]
+    fn simulator_logical_on_memory() {
+        let instructions = assemble_8086(
+            "mov word [0x100], 0x1234\n\
+             mov bx, 0x100\n\
+             and word [bx], 0xff\n\
+             or byte [bx + 2], 0x80\n\
+             not word [bx + 4]\n\
+             mov dx, 0xf0f0\n\
+             or dx, [bx]\n\
+             xor [bx + 4], dx\n\
+             test byte [bx + 2], 1",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        let word = |offset| simulator.memory.load_16(Address::new(0, offset));
+        assert_eq!(word(0x100), 0x34);
+        assert_eq!(word(0x102), 0x80);
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(0xf0f4));
+        assert_eq!(word(0x104), 0x0f0b);
+        assert!(simulator.flags.zero && !simulator.flags.carry);
+    }
+
+    #[test]
+    fn simulator_interrupts_through_vector_table() {
+        let instructions = assemble_8086(
+            "mov sp, 0x800\n\
+             int 0x20\n\
+             mov bl, 0\n\
+             div bl\n\
+             hlt\n\
+             mov ax, 0x1234\n\
+             iret\n\
+             mov dx, 0x55\n\
+             iret",
+        )
+        .unwrap();
+        let offset = |n: usize| {
+            instructions[..n]
+                .iter()
+                .map(|i| i.bytes().len())
+                .sum::<usize>()
+        };
+        let (int20, int0) = (offset(5) as u16, offset(7) as u16);
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.set_interrupt_vector(0, Address::new(0x1000, int0));
+        simulator.set_interrupt_vector(0x20, Address::new(0x1000, int20));
+        simulator.flags.interrupt = true;
+        simulator
+            .load_at(0x1000, 0, &instructions.try_into().unwrap())
+            .unwrap();
+        assert_eq!(simulator.exec(), Ok(StopReason::Halted));
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(0x1234));
+        // the divide error handler returned to the HLT after the division
+        assert_eq!(simulator.registers.get(Register::DX), Data::U16(0x55));
+        assert_eq!(simulator.registers.get(Register::SP), Data::U16(0x800));
+        assert!(simulator.flags.interrupt);
+    }
+
+    #[test]
+    fn simulator_vector_table_under_code() {
+        // the entry for interrupt 1 is the last two bytes of `mov bx` and the `int 1` itself
+        let program: Program = assemble_8086("mov ax, 0x1234\nmov bx, 0x5678\nint 1")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&program).unwrap();
+        let err = simulator.exec().unwrap_err();
+        assert_eq!(err.kind, SimErrorKind::UnhandledInterrupt(1));
+        assert_eq!(err.address, Address::new(0, 6));
+
+        let mut simulator = Simulator::default();
+        simulator.enable_vector_table();
+        simulator.load(&program).unwrap();
+        simulator.step().unwrap();
+        simulator.step().unwrap();
+        simulator.step().unwrap();
+        assert_eq!(simulator.code_addr(), Address::new(0x01cd, 0x5678));
+    }
+
+    #[test]
+    fn simulator_interrupt_hooks() {
+        let instructions = assemble_8086("int 0x21\nsti\nmov cx, 1\nint 0x30").unwrap();
+        let mut simulator = Simulator::default();
+        simulator.hook_interrupt(0x21, |registers, _, _| {
+            registers.set_imd(Register::BX, Data::U16(0x4c00))
+        });
+        simulator.hook_interrupt(0x08, |registers, _, _| {
+            registers.set_imd(Register::SI, registers.get(Register::CX))
+        });
+        simulator.raise_interrupt(0x08);
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        let err = simulator.exec().unwrap_err();
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(0x4c00));
+        // the timer interrupt waited for STI
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(0));
+        assert_eq!(simulator.registers.get(Register::CX), Data::U16(1));
+        assert_eq!(err.kind, SimErrorKind::UnhandledInterrupt(0x30));
+        assert_eq!(err.address, Address::new(0, 6));
+    }
+
+    #[test]
+    fn simulator_port_io() {
+        /// Latches the last byte written to each of its ports
+        struct Latch(std::rc::Rc<std::cell::RefCell<[u8; 2]>>);
+
+        impl IoBus for Latch {
+            fn read_u8(&mut self, port: u16) -> u8 {
+                self.0.borrow()[port as usize - 0x60]
+            }
+
+            fn write_u8(&mut self, port: u16, val: u8) {
+                self.0.borrow_mut()[port as usize - 0x60] = val;
+            }
+        }
+
+        let instructions = assemble_8086(
+            "mov ax, 0x1234\n\
+             out 0x60, ax\n\
+             mov dx, 0x61\n\
+             in al, dx\n\
+             mov dx, 0x3f8\n\
+             out dx, al\n\
+             in ax, dx",
+        )
+        .unwrap();
+        let latch = std::rc::Rc::new(std::cell::RefCell::new([0; 2]));
+        let mut bus = PortBus::default();
+        bus.attach(0x60..=0x61, Latch(latch.clone()));
+        let log = bus.log_accesses(3);
+        let mut simulator = Simulator::default();
+        simulator.enable_cycle_estimation();
+        simulator.io = Box::new(bus);
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(*latch.borrow(), [0x34, 0x12]);
+        // nothing is attached to the serial port, so it reads as all ones
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(0xffff));
+        // the log only keeps the last three accesses
+        assert_eq!(
+            log.accesses(),
+            [
+                PortAccess::ReadU8 {
+                    port: 0x61,
+                    value: 0x12
+                },
+                PortAccess::WriteU8 {
+                    port: 0x3f8,
+                    value: 0x12
+                },
+                PortAccess::ReadU16 {
+                    port: 0x3f8,
+                    value: 0xffff
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn simulator_shifts_and_rotates() {
+        let instructions = assemble_8086(
+            "mov word [0x100], 1\n\
+             mov ax, 0x8001\n\
+             shl ax, 1\n\
+             rcr word [0x100], 1\n\
+             mov bx, 0x8000\n\
+             mov cl, 15\n\
+             sar bx, cl\n\
+             mov si, 0x1234\n\
+             mov cl, 4\n\
+             ror si, cl\n\
+             mov dl, 0x81\n\
+             rol dl, 1\n\
+             mov cl, 0\n\
+             shl ax, cl",
+        )
+        .unwrap();
+        let mut simulator = Simulator::default();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        assert_eq!(simulator.registers.get(Register::AX), Data::U16(2));
+        assert_eq!(simulator.registers.get(Register::BX), Data::U16(0xffff));
+        assert_eq!(simulator.registers.get(Register::DL), Data::U8(0x03));
+        assert_eq!(simulator.registers.get(Register::SI), Data::U16(0x4123));
+        assert_eq!(simulator.memory.raw()[0x100..0x102], [0x00, 0x80]);
+        // the count of zero left the carry out of ROL alone
+        assert!(simulator.flags.carry);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn simulator_trace() {
+        let instructions = assemble_8086(
+            "mov cx, 200\n\
+             mov word [bp + 256], cx\n\
+             cmp cx, 200",
+        )
+        .unwrap();
+        let trace = SharedBuf::default();
+        let mut simulator = Simulator::default();
+        simulator.enable_trace(trace.clone());
+        simulator.enable_cycle_estimation();
+        simulator.load(&instructions.try_into().unwrap()).unwrap();
+        simulator.exec().unwrap();
+        let trace = String::from_utf8(trace.0.borrow().clone()).unwrap();
+        assert_eq!(
+            trace,
+            "mov cx, 200 ; Clocks: +4 = 4 | cx:0x0->0xc8 ip:0x0->0x3\n\
+             mov word [bp + 256], cx ; Clocks: +18 = 22 (ea: 9) | ip:0x3->0x7 \
+             mem[0x100]:0x0->0xc8\n\
+             cmp cx, 200 ; Clocks: +4 = 26 | ip:0x7->0xb flags:->PZ\n"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::{
+    cpu::{Address, Flags, MemoryWrite, RegisterChange},
+    disasm::Instruction,
+};
+
+/// Clocks charged for a single instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepCycles {
+    pub clocks_8086: usize,
+    pub clocks_8088: usize,
+    /// 8086 clocks so far, including this instruction
+    pub total_8086: usize,
+    /// part of the clocks spent calculating the effective address, if there is one
+    pub ea: Option<usize>,
+}
+
+/// What executing one instruction did
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub instruction: Instruction,
+    /// CS:IP the instruction was fetched from
+    pub address: Address,
+    /// word registers that changed, including ip, in display order
+    pub registers: Vec<RegisterChange>,
+    /// flags before and after, if any of them changed
+    pub flags: Option<(Flags, Flags)>,
+    pub memory: Vec<MemoryWrite>,
+    /// only there with cycle estimation enabled
+    pub cycles: Option<StepCycles>,
+}
+
+/// One line of the execution trace, like
+/// `mov cx, 200 ; Clocks: +4 = 4 | cx:0x0->0xc8 ip:0x0->0x3 flags:->Z`
+impl fmt::Display for StepResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ;", self.instruction)?;
+        if let Some(cycles) = self.cycles {
+            write!(
+                f,
+                " Clocks: +{} = {}",
+                cycles.clocks_8086, cycles.total_8086
+            )?;
+            if let Some(ea) = cycles.ea {
+                write!(f, " (ea: {})", ea)?;
+            }
+            write!(f, " |")?;
+        }
+        for change in &self.registers {
+            write!(f, " {}:{:#x}->{:#x}", change.name, change.old, change.new)?;
+        }
+        if let Some((before, after)) = self.flags {
+            write!(f, " flags:{}->{}", before.letters(), after.letters())?;
+        }
+        // like registers, bytes stored with the value they already had aren't changes
+        for write in self.memory.iter().filter(|write| write.old != write.new) {
+            write!(
+                f,
+                " mem[{:#x}]:{:#x}->{:#x}",
+                write.address, write.old, write.new
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use crate::cpu::Address;
+
+/// Something that ends `Simulator::exec`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    /// HLT was executed
+    Halt,
+    /// RET or RETF with no matching CALL, as if handing control back to whoever started the
+    /// program. The return itself isn't executed.
+    OutermostReturn,
+    /// CS:IP arrived at the address, after at least one instruction was executed
+    Address(Address),
+    /// the given number of instructions were executed by this call to `exec`
+    Instructions(usize),
+    /// the estimated 8086 clocks reached the given count. Adding it enables cycle estimation.
+    Cycles(usize),
+    /// CS:IP left the loaded program, going past its last byte or jumping elsewhere
+    EndOfCode,
+}
+
+/// Why `Simulator::exec` returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    Returned,
+    ReachedAddress(Address),
+    InstructionLimit(usize),
+    CycleLimit(usize),
+    EndOfCode,
+}
+
+/// Conditions `exec` checks, stopping at HLT, the outermost return or the end of the program
+/// unless told otherwise
+pub struct StopConditions(pub(super) Vec<StopCondition>);
+
+impl Default for StopConditions {
+    fn default() -> Self {
+        Self(vec![
+            StopCondition::Halt,
+            StopCondition::OutermostReturn,
+            StopCondition::EndOfCode,
+        ])
+    }
+}
+
+impl StopConditions {
+    pub(super) fn contains(&self, condition: StopCondition) -> bool {
+        self.0.contains(&condition)
+    }
+
+    /// Whether a condition needs clocks counted to ever hold
+    pub(super) fn counts_cycles(&self) -> bool {
+        self.0
+            .iter()
+            .any(|condition| matches!(condition, StopCondition::Cycles(_)))
+    }
+}
@@ -1,49 +1,80 @@
-use std::{fs::File, io::Read, process::Command};
-
-use sim8086::{decode_8086, instruction::Inst, simulator::Simulator, write_8086};
-
-fn run_nasm(filename: &str) -> Result<bool, std::io::Error> {
-    let status = Command::new("nasm").arg(filename).status()?;
-
-    Ok(status.success())
-}
-
-fn check_diff(file1: &str, file2: &str) -> Result<bool, std::io::Error> {
-    let status = Command::new("diff").arg(file1).arg(file2).status()?;
-
-    Ok(status.success())
-}
-
-fn decode(file_path: &str) -> Vec<Inst> {
-    let mut file = File::open(file_path).expect("Open file");
-
-    let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes).expect("read file");
+use std::{
+    fs::{self, File},
+    io::ErrorKind,
+    process::Command,
+};
+
+use sim8086::{assemble_8086, decode_8086, instruction::Inst, simulator::Simulator, write_8086};
+
+fn assemble(source: &str) -> Vec<u8> {
+    assemble_8086(source)
+        .expect("assemble")
+        .iter()
+        .flat_map(|inst| inst.bytes().to_vec())
+        .collect()
+}
+
+/// Assembles the source with NASM, or gives `None` when NASM isn't installed
+fn nasm(source: &str, name: &str) -> Option<Vec<u8>> {
+    let dir = std::env::temp_dir().join(format!("sim8086-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).expect("create nasm dir");
+    let (asm, bin) = (dir.join("in.asm"), dir.join("out"));
+    fs::write(&asm, source).expect("write nasm input");
+    let status = Command::new("nasm").arg("-o").arg(&bin).arg(&asm).status();
+    let bytes = match status {
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        status => {
+            assert!(
+                status.expect("run nasm").success(),
+                "nasm rejected {}",
+                name
+            );
+            Some(fs::read(&bin).expect("read nasm output"))
+        }
+    };
+    fs::remove_dir_all(&dir).expect("remove nasm dir");
+    bytes
+}
+
+/// Assembles the fixture, then checks that disassembling it gives back source that assembles to
+/// the very same bytes. With NASM installed, both the fixture and the disassembly are also
+/// assembled by NASM, which has to agree byte for byte.
+fn decode_test_fixture(name: &str) -> Vec<Inst> {
+    let source = fs::read_to_string(format!("tests/artifacts/{}.asm", name)).expect("read file");
+    let bytes = assemble(&source);
+    if let Some(reference) = nasm(&source, name) {
+        assert_eq!(bytes, reference, "{} assembled differently from nasm", name);
+    }
 
     let instructions = decode_8086(&bytes[..]);
-
-    let out_filepath = format!("{}.sim8086.asm", file_path);
-    let mut out_file = File::create(out_filepath).expect("Open output file");
-
-    write_8086(&instructions, &mut out_file).expect("Failed to write to output file");
+    let mut decoded = Vec::new();
+    write_8086(&instructions, &mut decoded).expect("Failed to write disassembly");
+    let decoded = String::from_utf8(decoded).expect("utf-8 disassembly");
+
+    assert_eq!(assemble(&decoded), bytes);
+    if let Some(reassembled) = nasm(&decoded, &format!("{}.sim8086", name)) {
+        assert_eq!(reassembled, bytes, "nasm reassembled {} differently", name);
+    }
     instructions
 }
 
-fn decode_test_fixture(name: &str) -> Vec<Inst> {
-    let fullpath = format!("tests/artifacts/{}", name);
-    let name = fullpath.as_str();
-    assert!(run_nasm(format!("{}.asm", name).as_str()).unwrap_or(false));
-    let instructions = decode(name);
-    assert!(run_nasm(format!("{}.sim8086.asm", name).as_str()).unwrap_or(false));
-    assert!(check_diff(name, format!("{}.sim8086", name).as_str()).unwrap_or(false));
-    instructions
+fn sim_test_fixture(name: &str) -> Simulator {
+    let instructions = decode_test_fixture(name);
+    let mut simulator = Simulator::default();
+    let program = instructions.try_into().expect("decoded properly");
+    simulator.load(&program).expect("program fits");
+    simulator.exec().expect("program runs");
+    simulator
 }
 
-fn sim_test_fixture(name: &str) -> Simulator {
+/// Like `sim_test_fixture`, but with the code loaded at `cs:0` so the program can draw into the
+/// bottom of memory without overwriting itself
+fn sim_test_fixture_in_segment(name: &str, cs: u16) -> Simulator {
     let instructions = decode_test_fixture(name);
     let mut simulator = Simulator::default();
-    let mut program = instructions.try_into().expect("decoded properly");
-    simulator.exec(&mut program);
+    let program = instructions.try_into().expect("decoded properly");
+    simulator.load_at(cs, 0, &program).expect("program fits");
+    simulator.exec().expect("program runs");
     simulator
 }
 
@@ -51,8 +82,9 @@ fn sim_test_fixture_with_clock_estimation(name: &str) -> Simulator {
     let instructions = decode_test_fixture(name);
     let mut simulator = Simulator::default();
     simulator.enable_cycle_estimation();
-    let mut program = instructions.try_into().expect("decoded properly");
-    simulator.exec(&mut program);
+    let program = instructions.try_into().expect("decoded properly");
+    simulator.load(&program).expect("program fits");
+    simulator.exec().expect("program runs");
     simulator
 }
 
@@ -236,13 +268,14 @@ fn simulate_memory_add_loop_challenge() {
 
 #[test]
 fn draw_rectangle() {
-    let mut sim = sim_test_fixture("listing_0054_draw_rectangle");
+    let mut sim = sim_test_fixture_in_segment("listing_0054_draw_rectangle", 0x0800);
     sim.enable_ip_log();
     let output = sim.to_string();
     let expected = r#"Final registers:
       cx: 0x0040 (64)
       dx: 0x0040 (64)
       bp: 0x4000 (16384)
+      cs: 0x0800 (2048)
       ip: 0x0026 (38)
    flags: PZ"#;
     assert_eq!(output.trim(), expected);
@@ -252,12 +285,13 @@ fn draw_rectangle() {
 
 #[test]
 fn challenge_rectangle() {
-    let mut sim = sim_test_fixture("listing_0055_challenge_rectangle");
+    let mut sim = sim_test_fixture_in_segment("listing_0055_challenge_rectangle", 0x0800);
     sim.enable_ip_log();
     let output = sim.to_string();
     let expected = r#"Final registers:
       bx: 0x4004 (16388)
       bp: 0x02fc (764)
+      cs: 0x0800 (2048)
       ip: 0x0044 (68)"#;
     assert_eq!(output.trim(), expected);
     sim.dump_memory(File::create("challenge_rectangle.data").unwrap())